@@ -3,6 +3,7 @@ use clap::{Args, Parser, Subcommand};
 use chrono::NaiveDate;
 use clap_complete::Shell;
 use crate::module::import::OrderTimeType;
+use crate::module::api::source_data::SourceEditStatus;
 
 #[derive(Parser)]
 #[command(bin_name = "hedge", name = "hedge", version, about = "Hedge Command Line Application")]
@@ -11,6 +12,8 @@ pub enum Cli {
     App,
     #[command(about = "Apply anywhere files for data updates")]
     Apply(Apply),
+    #[command(about = "Dump current server state into files which can be applied")]
+    Dump(Dump),
     #[command(subcommand, about = "Cli Channel control")]
     Channel(Channel),
     #[command(subcommand, about = "Background service process control")]
@@ -19,14 +22,21 @@ pub enum Cli {
     Import(Import),
     #[command(subcommand, about = "Source data management")]
     SourceData(SourceData),
+    #[command(subcommand, about = "Source tag mapping management")]
+    Mapping(Mapping),
     #[command(subcommand, about = "Toolbox")]
     Tool(Tool),
+    #[command(subcommand, about = "Local config management")]
+    Config(Config),
     #[command(about = "Generate shell completions")]
     Completion(Completion),
 }
 
 #[derive(Args)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct Apply {
+    #[command(subcommand)]
+    pub command: Option<ApplyCommand>,
     #[arg(short, long, help = "read from files in directory")]
     pub directory: Option<Vec<PathBuf>>,
     #[arg(short, long, help = "read from file")]
@@ -35,6 +45,36 @@ pub struct Apply {
     pub input: bool,
     #[arg(short, long, help = "print verbose output")]
     pub verbose: bool,
+    #[arg(long, help = "show changes against current server state without applying")]
+    pub dry_run: bool,
+    #[arg(long, help = "show changes against current server state before applying")]
+    pub diff: bool,
+    #[arg(long, conflicts_with_all = ["dry_run", "diff"], help = "check files for problems without connecting to server")]
+    pub check: bool,
+}
+
+#[derive(Subcommand)]
+pub enum ApplyCommand {
+    #[command(about = "Print JSON Schema of apply files")]
+    Schema,
+}
+
+#[derive(Args)]
+pub struct Dump {
+    #[arg(long, help = "dump tag tree")]
+    pub tags: bool,
+    #[arg(long, help = "dump topics")]
+    pub topics: bool,
+    #[arg(long, help = "dump authors")]
+    pub authors: bool,
+    #[arg(long, help = "dump settings")]
+    pub settings: bool,
+    #[arg(long, value_name = "HQL", help = "dump source data matched by hedge query language")]
+    pub source_data: Option<String>,
+    #[arg(short, long, help = "output directory")]
+    pub output: PathBuf,
+    #[arg(long, default_value = "yaml", value_parser = ["yaml", "json", "toml"], help = "output file format")]
+    pub format: String,
 }
 
 #[derive(Subcommand)]
@@ -102,8 +142,66 @@ pub enum SourceData {
         #[arg(long, help = "query offset", default_value_t = 0)]
         offset: u32
     },
+    #[command(about = "Show detail of source data")]
+    Show {
+        #[arg(help = "source site")]
+        site: String,
+        #[arg(help = "source id")]
+        id: String,
+        #[arg(long, help = "print as json")]
+        json: bool
+    },
+    #[command(about = "Export source data to file which can be applied")]
+    Export {
+        #[arg(help = "hedge query language")]
+        hql: String,
+        #[arg(short, long, help = "output file, json, yaml or toml")]
+        output: PathBuf
+    },
+    #[command(about = "Delete source data")]
+    Delete {
+        #[arg(required_unless_present = "query", requires = "ids", help = "source site")]
+        site: Option<String>,
+        #[arg(help = "source ids")]
+        ids: Vec<String>,
+        #[arg(long, conflicts_with = "site", help = "delete all source data matched by hedge query language")]
+        query: Option<String>,
+        #[arg(short, long, help = "skip confirmation")]
+        yes: bool
+    },
+    #[command(about = "Set status of source data in bulk")]
+    SetStatus {
+        #[arg(help = "target status")]
+        status: SourceEditStatus,
+        #[arg(long, help = "hedge query language")]
+        query: String,
+        #[arg(short, long, help = "skip confirmation")]
+        yes: bool
+    },
+    #[command(about = "Analyse source of filenames by source analyse rules")]
+    Analyse {
+        #[arg(required = true, help = "files, dirs or plain filenames")]
+        paths: Vec<PathBuf>
+    },
     #[command(about = "Download metadata for NOT_EDITED source data")]
-    Download,
+    Download {
+        #[arg(long, help = "only download source data of these sites. Default all available sites")]
+        site: Vec<String>,
+        #[arg(long, help = "only download source data in these status. Default NOT_EDITED and ERROR")]
+        status: Vec<SourceEditStatus>,
+        #[arg(long, help = "hedge query language")]
+        hql: Option<String>,
+        #[arg(long, help = "max count of source data to download. Default unlimited")]
+        limit: Option<u32>,
+        #[arg(long, help = "only download source data created since this date")]
+        since: Option<NaiveDate>,
+        #[arg(long, help = "skip source data that has failed this many times", default_value_t = 3)]
+        max_attempts: u32,
+        #[arg(long, help = "only use cached responses, without requesting source sites", conflicts_with = "no_cache")]
+        offline: bool,
+        #[arg(long, help = "ignore cached responses and request source sites again")]
+        no_cache: bool
+    },
     #[command(about = "Connect database to read metadata for NOT_EDITED source data")]
     Connect {
         #[arg(long, short, help = "query condition to split result")]
@@ -114,6 +212,87 @@ pub enum SourceData {
         update: bool,
         #[arg(short, long, help = "print verbose output")]
         verbose: bool,
+        #[arg(long = "var", value_parser = parse_key_value, help = "variable used in connect query, in form of key=value. Specify multiple times with same key as list")]
+        vars: Vec<(String, String)>,
+        #[arg(long, conflicts_with = "since", help = "continue from the last processed row of each split")]
+        resume: bool,
+        #[arg(long, help = "only pick up rows after the last successful run of each split")]
+        since: bool,
+        #[arg(long, help = "print parsed results as JSON Lines without contacting server")]
+        dry_run: bool
+    }
+}
+
+#[derive(Subcommand)]
+pub enum Mapping {
+    #[command(about = "Get meta tags mapped from source tag")]
+    Get {
+        #[arg(help = "source site")]
+        site: String,
+        #[arg(help = "source tag type")]
+        tag_type: String,
+        #[arg(help = "source tag code")]
+        code: String
+    },
+    #[command(about = "Set meta tags mapped from source tag")]
+    Set {
+        #[arg(help = "source site")]
+        site: String,
+        #[arg(help = "source tag type")]
+        tag_type: String,
+        #[arg(help = "source tag code")]
+        code: String,
+        #[arg(required = true, help = "mapping targets, in form of tag:<id>, topic:<id> or author:<id>")]
+        targets: Vec<String>
+    },
+    #[command(about = "Delete all mappings of source tag")]
+    Delete {
+        #[arg(help = "source site")]
+        site: String,
+        #[arg(help = "source tag type")]
+        tag_type: String,
+        #[arg(help = "source tag code")]
+        code: String
+    },
+    #[command(about = "Query mappings of multiple source tags")]
+    Query {
+        #[arg(help = "source site")]
+        site: String,
+        #[arg(required = true, help = "source tags, in form of <type>:<code>")]
+        tags: Vec<String>
+    },
+    #[command(about = "Export mappings of all source tags used by source data of site")]
+    Export {
+        #[arg(help = "source site")]
+        site: String,
+        #[arg(short, long, help = "output file, json or yaml. Default print to stdout")]
+        output: Option<PathBuf>,
+        #[arg(long, help = "only export source tags without mapping")]
+        unmapped: bool
+    },
+    #[command(about = "Report most frequent source tags without mapping")]
+    Unmapped {
+        #[arg(long, help = "source site")]
+        site: String,
+        #[arg(long, help = "only report source tags used at least this many times", default_value_t = 1)]
+        min_count: u32,
+        #[arg(long, help = "max count of source tags to report")]
+        limit: Option<usize>,
+        #[arg(short, long, help = "write a stub apply file of tags, topics and authors mapped from reported source tags")]
+        output: Option<PathBuf>
+    }
+}
+
+#[derive(Subcommand)]
+pub enum Config {
+    #[command(about = "Validate connect parser selectors against the first rows of query")]
+    CheckConnect {
+        #[arg(long, short, required = true, help = "query condition to split result")]
+        split: Vec<String>,
+        #[arg(long, help = "number of rows to check", default_value_t = 20)]
+        rows: u32,
+        #[arg(long = "var", value_parser = parse_key_value, help = "variable used in connect query, in form of key=value. Specify multiple times with same key as list")]
+        vars: Vec<(String, String)>
     }
 }
 
@@ -127,6 +306,13 @@ pub enum Tool {
         tree: Option<PathBuf>,
         #[arg(long, help = "dry run")]
         dry_run: bool
+    },
+    #[command(about = "Test source analyse rules locally without server. Builtin rules of server are not included")]
+    TestRules {
+        #[arg(short, long, help = "apply file which contains setting.import.sourceAnalyseRules")]
+        rules: PathBuf,
+        #[arg(required = true, help = "filenames to analyse")]
+        filenames: Vec<String>
     }
 }
 
@@ -134,4 +320,11 @@ pub enum Tool {
 pub struct Completion {
     #[arg(help = "shell type")]
     pub shell: Shell
-}
\ No newline at end of file
+}
+
+fn parse_key_value(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((k, v)) if !k.is_empty() => Result::Ok((k.to_string(), v.to_string())),
+        _ => Result::Err(format!("'{}' is not in form of key=value", s))
+    }
+}
@@ -1,7 +1,13 @@
-use std::{path::PathBuf, io::stdin, error::Error};
-use serde::Deserialize;
-use crate::{module::api::{bulk::{AuthorBulkForm, BulkModule, SourceDataBulkForm, TagBulkForm, TopicBulkForm}, setting::{FindSimilarOptionUpdateForm, ImportOptionUpdateForm, MetaOptionUpdateForm, QueryOptionUpdateForm, ServerOptionUpdateForm, SettingModule, SourceSiteUpdateForm, StorageOptionUpdateForm}}, utils::error::ApplicationError};
-use super::Context;
+use std::{path::{Path, PathBuf}, io::stdin, error::Error, collections::HashMap};
+use serde::{Serialize, Deserialize, de::DeserializeOwned};
+use serde_json::{json, Value};
+use schemars::{JsonSchema, schema::{RootSchema, Schema}, schema_for};
+use crate::module::api::{bulk::{AuthorBulkForm, BulkModule, MappingSourceTagForm, SourceDataBulkForm, TagBulkForm, TopicBulkForm}, setting::{FindSimilarOptionUpdateForm, ImportOptionUpdateForm, MetaOptionUpdateForm, QueryOptionUpdateForm, ServerOptionUpdateForm, SettingModule, SourceSiteUpdateForm, StorageOptionUpdateForm}};
+use crate::module::api::{meta::{AuthorDetailRes, AuthorRes, MappingSourceTagDto, MetaModule, TagDetailRes, TagTreeNode, TopicDetailRes, TopicRes}, source_data::SourceDataModule};
+use crate::module::server::ServerManager;
+use crate::module::api::{bulk::{AdditionalInfoForm, SourceBookForm, SourceTagForm}, setting::{AdditionalInfo, FindSimilarTaskConfig, FindSimilarTaskConfigSourceTagType, SourceAnalyseRule, SourceAnalyseRuleExtra}};
+use crate::utils::{diff::{array_diff, diff_fields, FieldChange}, error::{ApiResultError, ApplicationError}, schema::add_aliases, location::{Location, locate_key, offset_to_location}};
+use super::{Context, source_data::detail_to_bulk_form};
 
 pub enum ApplyInputType {
     Directory(PathBuf),
@@ -9,7 +15,14 @@ pub enum ApplyInputType {
     Input
 }
 
-pub async fn apply(context: &mut Context<'_>, input: &Vec<ApplyInputType>, verbose: bool) {
+pub struct ApplyOptions {
+    pub verbose: bool,
+    pub dry_run: bool,
+    pub diff: bool
+}
+
+pub async fn apply(context: &mut Context<'_>, input: &Vec<ApplyInputType>, options: &ApplyOptions) {
+    let ApplyOptions { verbose, dry_run, diff } = *options;
     if let Err(e) = context.server_manager.maintaining_for_start().await {
         eprintln!("Cannot establish connection to server. {}", e);
         return
@@ -23,6 +36,17 @@ pub async fn apply(context: &mut Context<'_>, input: &Vec<ApplyInputType>, verbo
         }
     };
 
+    if dry_run || diff {
+        if let Err(e) = print_diff(context.server_manager, &file, verbose).await {
+            eprintln!("Error occrred in requesting. {}", e);
+            return
+        }
+        if dry_run {
+            return
+        }
+        println!("---");
+    }
+
     let mut bulk_module = BulkModule::new(&context.server_manager);
 
     if let Some(source_data) = file.source_data {
@@ -133,12 +157,893 @@ pub async fn apply(context: &mut Context<'_>, input: &Vec<ApplyInputType>, verbo
     }
 }
 
+/// 取得apply文件中涉及的每一项在服务器上的当前状态，打印字段级的变化。不会写入任何内容。
+async fn print_diff(server_manager: &ServerManager, file: &ApplyFile, verbose: bool) -> Result<(), Box<dyn Error>> {
+    let mut printer = DiffPrinter::new(verbose);
+    if let Some(ref source_data) = file.source_data {
+        printer.section("Source data");
+        diff_source_data(server_manager, source_data, &mut printer).await?;
+    }
+    if let Some(ref tags) = file.tags {
+        printer.section("Tags");
+        diff_tags(server_manager, tags, &mut printer).await?;
+    }
+    if let Some(ref topics) = file.topics {
+        printer.section("Topics");
+        diff_topics(server_manager, topics, &mut printer).await?;
+    }
+    if let Some(ref authors) = file.authors {
+        printer.section("Authors");
+        diff_authors(server_manager, authors, &mut printer).await?;
+    }
+    if let Some(ref setting) = file.setting {
+        printer.section("Setting");
+        diff_setting(server_manager, setting, &mut printer).await?;
+    }
+    printer.summary();
+    Result::Ok(())
+}
+
+async fn diff_source_data(server_manager: &ServerManager, source_data: &[SourceDataBulkForm], printer: &mut DiffPrinter) -> Result<(), Box<dyn Error>> {
+    const SKIP: [&str; 2] = ["sourceSite", "sourceId"];
+    let mut source_data_module = SourceDataModule::new(server_manager);
+    for form in source_data {
+        let name = format!("{}-{}", form.source_site, form.source_id);
+        let new = serde_json::to_value(form)?;
+        match source_data_module.get(&form.source_site, &form.source_id).await {
+            Ok(detail) => {
+                let current = serde_json::to_value(detail_to_bulk_form(detail))?;
+                printer.update(&name, &diff_fields(&new, &current, &SKIP));
+            },
+            Err(e) => {
+                let code = e.downcast_ref::<ApiResultError>().map(|e| e.code.clone());
+                match code.as_deref() {
+                    Some("NOT_FOUND") => printer.create(&name, &new, &SKIP),
+                    Some(_) => printer.error(&name, &e.to_string()),
+                    None => return Result::Err(e)
+                }
+            }
+        }
+    }
+    Result::Ok(())
+}
+
+struct TagPlan<'a> {
+    form: &'a TagBulkForm,
+    address: String,
+    id: Option<i32>
+}
+
+async fn diff_tags(server_manager: &ServerManager, tags: &[TagBulkForm], printer: &mut DiffPrinter) -> Result<(), Box<dyn Error>> {
+    const SKIP: [&str; 3] = ["name", "rename", "children"];
+    let mut meta_module = MetaModule::new(server_manager);
+    let tree = meta_module.tag_tree().await?;
+    let mut addresses: HashMap<i32, String> = HashMap::new();
+    collect_tag_addresses(&tree, Option::None, &mut addresses);
+    let mut plans: Vec<TagPlan> = Vec::new();
+    plan_tags(tags.iter().collect(), Option::Some(&tree), Option::None, &mut plans);
+
+    for plan in plans {
+        let mut new = serde_json::to_value(plan.form)?;
+        match plan.id {
+            None => if plan.form.rename.is_some() {
+                printer.error(&plan.address, "Not found, so it cannot be renamed.")
+            }else{
+                printer.create(&plan.address, &new, &SKIP)
+            },
+            Some(id) => {
+                let current = tag_detail_to_bulk_form(meta_module.get_tag(id).await?, &addresses);
+                //link可以只写地址的末尾几段，因此按后缀将其对齐到完整地址再比较
+                let current_links = current.links.clone().unwrap_or_default();
+                if let Some(Value::Array(links)) = new.get_mut("links") {
+                    for link in links.iter_mut() {
+                        let address = link.as_str().and_then(|l| current_links.iter().find(|a| *a == l || a.ends_with(&format!(".{}", l)))).cloned();
+                        if let Some(address) = address {
+                            *link = Value::String(address)
+                        }
+                    }
+                }
+                let mut changes = diff_fields(&new, &serde_json::to_value(&current)?, &SKIP);
+                if let Some(c) = rename_change(&plan.form.name, &plan.form.rename) {
+                    changes.insert(0, c)
+                }
+                printer.update(&plan.address, &changes);
+            }
+        }
+    }
+    Result::Ok(())
+}
+
+/// 按服务器定位标签的方式，逐级按名称在标签树中找到每个表单对应的标签。
+fn plan_tags<'a>(forms: Vec<&'a TagBulkForm>, nodes: Option<&[TagTreeNode]>, parent: Option<&str>, plans: &mut Vec<TagPlan<'a>>) {
+    for form in forms {
+        let address = match parent { Some(p) => format!("{}.{}", p, form.name), None => form.name.clone() };
+        let node = nodes.and_then(|n| n.iter().find(|n| n.name == form.name));
+        plans.push(TagPlan { form, address: address.clone(), id: node.map(|n| n.id) });
+        //给出rename却找不到目标时，服务器会跳过此项及其子项
+        if node.is_some() || form.rename.is_none() {
+            if let Some(ref children) = form.children {
+                plan_tags(children.iter().map(|c| c.as_ref()).collect(), node.and_then(|n| n.children.as_deref()), Option::Some(&address), plans);
+            }
+        }
+    }
+}
+
+pub fn collect_tag_addresses(nodes: &[TagTreeNode], parent: Option<&str>, addresses: &mut HashMap<i32, String>) {
+    for node in nodes {
+        let address = match parent { Some(p) => format!("{}.{}", p, node.name), None => node.name.clone() };
+        if let Some(ref children) = node.children {
+            collect_tag_addresses(children, Option::Some(&address), addresses);
+        }
+        addresses.insert(node.id, address);
+    }
+}
+
+struct TopicPlan<'a> {
+    form: &'a TopicBulkForm,
+    path: String,
+    id: Option<i32>,
+    parent_change: Option<FieldChange>
+}
+
+async fn diff_topics(server_manager: &ServerManager, topics: &[TopicBulkForm], printer: &mut DiffPrinter) -> Result<(), Box<dyn Error>> {
+    const SKIP: [&str; 3] = ["name", "rename", "children"];
+    let mut meta_module = MetaModule::new(server_manager);
+    let records = list_all_topics(&mut meta_module).await?;
+    let mut plans: Vec<TopicPlan> = Vec::new();
+    plan_topics(topics.iter().collect(), &records, Option::None, Option::None, &mut plans);
+
+    for plan in plans {
+        let new = serde_json::to_value(plan.form)?;
+        match plan.id {
+            None => if plan.form.rename.is_some() {
+                printer.error(&plan.path, "Not found, so it cannot be renamed.")
+            }else{
+                printer.create(&plan.path, &new, &SKIP)
+            },
+            Some(id) => {
+                let current = topic_detail_to_bulk_form(meta_module.get_topic(id).await?);
+                let mut changes = diff_fields(&new, &serde_json::to_value(&current)?, &SKIP);
+                if let Some(c) = plan.parent_change {
+                    changes.insert(0, c)
+                }
+                if let Some(c) = rename_change(&plan.form.name, &plan.form.rename) {
+                    changes.insert(0, c)
+                }
+                printer.update(&plan.path, &changes);
+            }
+        }
+    }
+    Result::Ok(())
+}
+
+/// 按服务器定位主题的方式，以名称和根节点(IP/COPYRIGHT类型的祖先)找到每个表单对应的主题。
+/// parent为(id, 路径, 名称)。parent与root中的id为None时，表示它是将要新建的主题。
+fn plan_topics<'a>(forms: Vec<&'a TopicBulkForm>, records: &[TopicRes], parent: Option<(Option<i32>, &str, &str)>, root: Option<(Option<i32>, String)>, plans: &mut Vec<TopicPlan<'a>>) {
+    for form in forms {
+        let path = match parent { Some((_, p, _)) => format!("{}/{}", p, form.name), None => form.name.clone() };
+        let record = records.iter().find(|r| r.name == form.name && match root {
+            None => r.parent_root.is_none(),
+            Some((Some(id), _)) => r.parent_root.as_ref().map(|p| p.id) == Option::Some(id),
+            Some((None, _)) => false
+        });
+        //bulk会把主题移动到表单中的父节点下，顶层的表单会被移动到根
+        let parent_change = record.and_then(|r| {
+            let parent_id = parent.and_then(|(id, _, _)| id);
+            if r.parent_id == parent_id && (parent.is_none() || parent_id.is_some()) {
+                Option::None
+            }else{
+                let old = r.parent_id.and_then(|id| records.iter().find(|t| t.id == id)).map(|t| json!(t.name)).unwrap_or(Value::Null);
+                let new = parent.map(|(_, _, n)| json!(n)).unwrap_or(Value::Null);
+                Option::Some(FieldChange { field: "parent".to_string(), old, new })
+            }
+        });
+        let id = record.map(|r| r.id);
+        plans.push(TopicPlan { form, path: path.clone(), id, parent_change });
+
+        if record.is_none() && form.rename.is_some() { continue }
+        if let Some(ref children) = form.children {
+            let topic_type = form.tag_type.clone().or_else(|| record.map(|r| r.tag_type.clone())).unwrap_or_else(|| "UNKNOWN".to_string());
+            let is = |t: &str, v: &str| t.eq_ignore_ascii_case(v);
+            let next_root = match root {
+                Some((_, ref t)) if is(t, "IP") => root.clone(),
+                Some((_, ref t)) if is(t, "COPYRIGHT") && is(&topic_type, "IP") => Option::Some((id, topic_type)),
+                None if is(&topic_type, "IP") || is(&topic_type, "COPYRIGHT") => Option::Some((id, topic_type)),
+                _ => root.clone()
+            };
+            plan_topics(children.iter().map(|c| c.as_ref()).collect(), records, Option::Some((id, &path, &form.name)), next_root, plans);
+        }
+    }
+}
+
+async fn diff_authors(server_manager: &ServerManager, authors: &[AuthorBulkForm], printer: &mut DiffPrinter) -> Result<(), Box<dyn Error>> {
+    const SKIP: [&str; 2] = ["name", "rename"];
+    let mut meta_module = MetaModule::new(server_manager);
+    let records = list_all_authors(&mut meta_module).await?;
+
+    for form in authors {
+        let new = serde_json::to_value(form)?;
+        match records.iter().find(|r| r.name == form.name) {
+            None => if form.rename.is_some() {
+                printer.error(&form.name, "Not found, so it cannot be renamed.")
+            }else{
+                printer.create(&form.name, &new, &SKIP)
+            },
+            Some(record) => {
+                let current = author_detail_to_bulk_form(meta_module.get_author(record.id).await?);
+                let mut changes = diff_fields(&new, &serde_json::to_value(&current)?, &SKIP);
+                if let Some(c) = rename_change(&form.name, &form.rename) {
+                    changes.insert(0, c)
+                }
+                printer.update(&form.name, &changes);
+            }
+        }
+    }
+    Result::Ok(())
+}
+
+async fn diff_setting(server_manager: &ServerManager, setting: &ApplyFileSetting, printer: &mut DiffPrinter) -> Result<(), Box<dyn Error>> {
+    let mut setting_module = SettingModule::new(server_manager);
+    diff_option(&mut setting_module, printer, "meta", "meta", &setting.meta).await?;
+    diff_option(&mut setting_module, printer, "query", "query", &setting.query).await?;
+    diff_option(&mut setting_module, printer, "find_similar", "find-similar", &setting.find_similar).await?;
+    diff_option(&mut setting_module, printer, "storage", "storage", &setting.storage).await?;
+    if let Some(ref sites) = setting.source_sites {
+        //source_sites是整体提交的，未出现在表单中的站点会被删除
+        let current = setting_module.get_source_sites_value().await?;
+        for site in sites {
+            let name = format!("source_sites.{}", site.name);
+            let new = serde_json::to_value(site)?;
+            match current.iter().find(|c| c.get("name").and_then(|n| n.as_str()) == Option::Some(site.name.as_str())) {
+                Some(c) => printer.update(&name, &diff_fields(&new, c, &["name"])),
+                None => printer.create(&name, &new, &["name"])
+            }
+        }
+        for c in current.iter() {
+            if let Some(n) = c.get("name").and_then(|n| n.as_str()) {
+                if !sites.iter().any(|s| s.name == n) {
+                    printer.remove(&format!("source_sites.{}", n))
+                }
+            }
+        }
+    }
+    diff_option(&mut setting_module, printer, "import", "import", &setting.import).await?;
+    diff_option(&mut setting_module, printer, "server", "server", &setting.server).await?;
+    Result::Ok(())
+}
+
+async fn diff_option<T: Serialize>(setting_module: &mut SettingModule<'_>, printer: &mut DiffPrinter, name: &str, path: &str, form: &Option<T>) -> Result<(), Box<dyn Error>> {
+    if let Some(form) = form {
+        let current = setting_module.get_option(path).await?;
+        printer.update(name, &diff_fields(&serde_json::to_value(form)?, &current, &[]));
+    }
+    Result::Ok(())
+}
+
+fn rename_change(name: &str, rename: &Option<String>) -> Option<FieldChange> {
+    match rename {
+        Some(r) if r != name => Option::Some(FieldChange { field: "name".to_string(), old: json!(name), new: json!(r) }),
+        _ => Option::None
+    }
+}
+
+pub async fn list_all_topics(meta_module: &mut MetaModule<'_>) -> Result<Vec<TopicRes>, Box<dyn Error>> {
+    const PAGE_SIZE: u32 = 1000;
+    let mut items: Vec<TopicRes> = Vec::new();
+    loop {
+        let r = meta_module.list_topics(items.len() as u32, PAGE_SIZE).await?;
+        let page_size = r.result.len() as u32;
+        items.extend(r.result);
+        if page_size < PAGE_SIZE || items.len() as i32 >= r.total { break }
+    }
+    Result::Ok(items)
+}
+
+pub async fn list_all_authors(meta_module: &mut MetaModule<'_>) -> Result<Vec<AuthorRes>, Box<dyn Error>> {
+    const PAGE_SIZE: u32 = 1000;
+    let mut items: Vec<AuthorRes> = Vec::new();
+    loop {
+        let r = meta_module.list_authors(items.len() as u32, PAGE_SIZE).await?;
+        let page_size = r.result.len() as u32;
+        items.extend(r.result);
+        if page_size < PAGE_SIZE || items.len() as i32 >= r.total { break }
+    }
+    Result::Ok(items)
+}
+
+/// 将标签详情转换为bulk表单。links按addresses转换为完整地址。
+pub fn tag_detail_to_bulk_form(detail: TagDetailRes, addresses: &HashMap<i32, String>) -> TagBulkForm {
+    TagBulkForm {
+        name: detail.name,
+        rename: Option::None,
+        other_names: Option::Some(detail.other_names),
+        tag_type: Option::Some(detail.tag_type),
+        is_sequence_group: Option::Some(detail.is_sequence_group),
+        is_override_group: Option::Some(detail.is_override_group),
+        description: Option::Some(detail.description),
+        links: Option::Some(detail.links.iter().filter_map(|l| addresses.get(&l.id).cloned()).collect()),
+        color: detail.color,
+        mapping_source_tags: Option::Some(mapping_to_forms(detail.mapping_source_tags)),
+        children: Option::None
+    }
+}
+
+pub fn topic_detail_to_bulk_form(detail: TopicDetailRes) -> TopicBulkForm {
+    TopicBulkForm {
+        name: detail.name,
+        rename: Option::None,
+        other_names: Option::Some(detail.other_names),
+        tag_type: Option::Some(detail.tag_type),
+        keywords: Option::Some(detail.keywords),
+        description: Option::Some(detail.description),
+        favorite: Option::Some(detail.favorite),
+        score: detail.score,
+        mapping_source_tags: Option::Some(mapping_to_forms(detail.mapping_source_tags)),
+        children: Option::None
+    }
+}
+
+pub fn author_detail_to_bulk_form(detail: AuthorDetailRes) -> AuthorBulkForm {
+    AuthorBulkForm {
+        name: detail.name,
+        rename: Option::None,
+        other_names: Option::Some(detail.other_names),
+        tag_type: Option::Some(detail.tag_type),
+        keywords: Option::Some(detail.keywords),
+        description: Option::Some(detail.description),
+        favorite: Option::Some(detail.favorite),
+        score: detail.score,
+        mapping_source_tags: Option::Some(mapping_to_forms(detail.mapping_source_tags))
+    }
+}
+
+fn mapping_to_forms(tags: Vec<MappingSourceTagDto>) -> Vec<MappingSourceTagForm> {
+    tags.into_iter().map(|t| MappingSourceTagForm { site: t.site, code: t.code, name: t.name, other_name: t.other_name, tag_type: Option::Some(t.tag_type) }).collect()
+}
+
+/// 打印每一项的变化，并统计各类变化的数量。
+struct DiffPrinter {
+    verbose: bool,
+    created: u32,
+    updated: u32,
+    removed: u32,
+    unchanged: u32,
+    errors: u32
+}
+
+impl DiffPrinter {
+    fn new(verbose: bool) -> DiffPrinter {
+        DiffPrinter { verbose, created: 0, updated: 0, removed: 0, unchanged: 0, errors: 0 }
+    }
+    fn section(&self, title: &str) {
+        println!("\x1b[1m{}\x1b[0m", title);
+    }
+    fn create(&mut self, name: &str, form: &Value, skip: &[&str]) {
+        self.created += 1;
+        println!("  \x1b[32m+ {}\x1b[0m", name);
+        if let Value::Object(fields) = form {
+            for (k, v) in fields.iter() {
+                if !skip.contains(&k.as_str()) {
+                    println!("      {}: \x1b[32m{}\x1b[0m", k, v);
+                }
+            }
+        }
+    }
+    fn update(&mut self, name: &str, changes: &[FieldChange]) {
+        if changes.is_empty() {
+            self.unchanged += 1;
+            if self.verbose {
+                println!("  = {}", name);
+            }
+            return
+        }
+        self.updated += 1;
+        println!("  \x1b[33m~ {}\x1b[0m", name);
+        for c in changes {
+            if let (Value::Array(new), Value::Array(old)) = (&c.new, &c.old) {
+                println!("      {}:", c.field);
+                let (added, removed) = array_diff(new, old);
+                for v in removed {
+                    println!("        \x1b[31m- {}\x1b[0m", v);
+                }
+                for v in added {
+                    println!("        \x1b[32m+ {}\x1b[0m", v);
+                }
+            }else{
+                println!("      {}: \x1b[31m{}\x1b[0m -> \x1b[32m{}\x1b[0m", c.field, c.old, c.new);
+            }
+        }
+    }
+    fn remove(&mut self, name: &str) {
+        self.removed += 1;
+        println!("  \x1b[31m- {}\x1b[0m", name);
+    }
+    fn error(&mut self, name: &str, message: &str) {
+        self.errors += 1;
+        println!("  \x1b[1;31m! {}\x1b[0m {}", name, message);
+    }
+    fn summary(&self) {
+        println!("---");
+        println!("{} to create, {} to update, {} to remove, {} unchanged, {} errors.", self.created, self.updated, self.removed, self.unchanged, self.errors);
+    }
+}
+
+/// 打印apply文件的JSON Schema。schema由表单类型生成，字段别名也一并加入。
+pub fn schema() {
+    let (root, _) = apply_file_schema();
+    println!("{}", serde_json::to_string_pretty(&root).unwrap());
+}
+
+/// 生成apply文件的schema，并返回已加入别名的定义名称。
+/// 别名需要逐个类型加入，新增表单类型时也要加到这里，否则测试会报告遗漏的定义。
+fn apply_file_schema() -> (RootSchema, Vec<String>) {
+    let mut root = schema_for!(ApplyFile);
+    add_aliases::<ApplyFile>(&mut root.schema);
+    let mut added = Vec::new();
+    add_definition_aliases::<ApplyFileSetting>(&mut root, &mut added);
+    add_definition_aliases::<SourceDataBulkForm>(&mut root, &mut added);
+    add_definition_aliases::<SourceTagForm>(&mut root, &mut added);
+    add_definition_aliases::<SourceBookForm>(&mut root, &mut added);
+    add_definition_aliases::<AdditionalInfoForm>(&mut root, &mut added);
+    add_definition_aliases::<TagBulkForm>(&mut root, &mut added);
+    add_definition_aliases::<TopicBulkForm>(&mut root, &mut added);
+    add_definition_aliases::<AuthorBulkForm>(&mut root, &mut added);
+    add_definition_aliases::<MappingSourceTagForm>(&mut root, &mut added);
+    add_definition_aliases::<MetaOptionUpdateForm>(&mut root, &mut added);
+    add_definition_aliases::<QueryOptionUpdateForm>(&mut root, &mut added);
+    add_definition_aliases::<ImportOptionUpdateForm>(&mut root, &mut added);
+    add_definition_aliases::<StorageOptionUpdateForm>(&mut root, &mut added);
+    add_definition_aliases::<ServerOptionUpdateForm>(&mut root, &mut added);
+    add_definition_aliases::<FindSimilarOptionUpdateForm>(&mut root, &mut added);
+    add_definition_aliases::<FindSimilarTaskConfig>(&mut root, &mut added);
+    add_definition_aliases::<FindSimilarTaskConfigSourceTagType>(&mut root, &mut added);
+    add_definition_aliases::<SourceSiteUpdateForm>(&mut root, &mut added);
+    add_definition_aliases::<AdditionalInfo>(&mut root, &mut added);
+    add_definition_aliases::<SourceAnalyseRule>(&mut root, &mut added);
+    add_definition_aliases::<SourceAnalyseRuleExtra>(&mut root, &mut added);
+    (root, added)
+}
+
+fn add_definition_aliases<T: JsonSchema + DeserializeOwned>(root: &mut RootSchema, added: &mut Vec<String>) {
+    if let Some(Schema::Object(o)) = root.definitions.get_mut(&T::schema_name()) {
+        add_aliases::<T>(o);
+        added.push(T::schema_name());
+    }
+}
+
+/// 可用的颜色，与服务器的定义保持一致。
+const USEFUL_COLORS: [&str; 12] = ["green", "blue", "skyblue", "teal", "cyan", "yellow", "red", "orange", "pink", "deeppink", "tea", "brown"];
+
+/// 不连接服务器，检查所有输入文件，报告每一个问题所在的文件与位置。
+pub fn check(input: &Vec<ApplyInputType>) {
+    let mut checker = Checker { sources: Vec::new(), problems: Vec::new(), names: HashMap::new() };
+    for i in input {
+        match i {
+            ApplyInputType::Directory(d) => match list_directory(d) {
+                Ok(files) => for f in files { checker.read_file(&f) },
+                Err(e) => checker.add_source(d.to_str().unwrap().to_string(), String::new(), Option::None, vec![(Option::None, format!("Cannot read directory. {}", e))])
+            },
+            ApplyInputType::File(f) => checker.read_file(f),
+            ApplyInputType::Input => checker.read_input()
+        }
+    }
+    if checker.sources.is_empty() {
+        eprintln!("Apply input read error. apply files is empty.");
+        return
+    }
+
+    checker.check_meta_colors();
+    checker.check_tags();
+    checker.check_topics();
+    checker.check_authors();
+    checker.check_reduce();
+    if !checker.report() {
+        std::process::exit(1)
+    }
+}
+
+/// 检查时读取的一个输入。无法解析时file为None。
+struct CheckSource {
+    name: String,
+    text: String,
+    file: Option<ApplyFile>
+}
+
+struct Problem {
+    source: usize,
+    location: Option<Location>,
+    message: String
+}
+
+/// 展开后的一个表单。address为表单声明的地址，final_address为执行rename之后的地址。
+/// nth是同一文件中相同name出现的次序，用于在文本中找到它的位置。
+struct CheckEntry<'a, T> {
+    source: usize,
+    nth: usize,
+    form: &'a T,
+    rename: Option<&'a str>,
+    address: String,
+    final_address: String,
+    root: bool
+}
+
+type ParseError = (Option<Location>, String);
+
+struct Checker {
+    sources: Vec<CheckSource>,
+    problems: Vec<Problem>,
+    names: HashMap<(usize, String), usize>
+}
+
+impl Checker {
+    fn read_file(&mut self, f: &Path) {
+        let name = f.to_str().unwrap().to_string();
+        let extension = f.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+        match std::fs::read_to_string(f) {
+            Err(e) => self.add_source(name, String::new(), Option::None, vec![(Option::None, format!("Cannot read file. {}", e))]),
+            Ok(text) => self.add_text(name, text, &extension)
+        }
+    }
+    fn read_input(&mut self) {
+        let lines: Vec<String> = stdin().lines().map(|f| f.unwrap()).collect();
+        let text = lines.join("\n");
+        //按照能否解析为通用的值来判断格式，再以该格式报告错误
+        let extension = if serde_json::from_str::<Value>(&text).is_ok() {
+            "json"
+        }else if toml::from_str::<toml::Table>(&text).is_ok() {
+            "toml"
+        }else{
+            "yaml"
+        };
+        self.add_text("<stdin>".to_string(), text, extension)
+    }
+    /// 解析并加入一个输入。文件无法完整解析时逐项解析，使一个错误的项不会掩盖其他项的问题。
+    fn add_text(&mut self, name: String, text: String, extension: &str) {
+        let (file, errors) = match parse_apply_file(&text, extension) {
+            Ok(f) => (Option::Some(f), Vec::new()),
+            Err(e) => match parse_apply_value(&text, extension) {
+                Some(value) => {
+                    let (file, errors) = parse_apply_entries(&text, value);
+                    //逐项解析时没有发现错误，说明错误出在逐项解析无法覆盖的地方，仍报告原本的错误
+                    (Option::Some(file), if errors.is_empty() { vec![e] }else{ errors })
+                },
+                None => (Option::None, vec![e])
+            }
+        };
+        self.add_source(name, text, file, errors)
+    }
+    fn add_source(&mut self, name: String, text: String, file: Option<ApplyFile>, errors: Vec<ParseError>) {
+        let source = self.sources.len();
+        self.problems.extend(errors.into_iter().map(|(location, message)| Problem { source, location, message }));
+        self.sources.push(CheckSource { name, text, file });
+    }
+    /// 表单的位置即是它的name所在的位置。
+    fn locate<T>(&self, entry: &CheckEntry<T>, name: &str) -> Option<Location> {
+        locate_key(&self.sources[entry.source].text, &["name"], Option::Some(name), entry.nth)
+    }
+    fn display_location(&self, source: usize, location: Option<Location>) -> String {
+        match location {
+            Some((line, column)) => format!("{}:{}:{}", self.sources[source].name, line, column),
+            None => self.sources[source].name.clone()
+        }
+    }
+
+    fn check_meta_colors(&mut self) {
+        let mut problems = Vec::new();
+        for (source, s) in self.sources.iter().enumerate() {
+            let Some(meta) = s.file.as_ref().and_then(|f| f.setting.as_ref()).and_then(|s| s.meta.as_ref()) else { continue };
+            for (field, colors) in [("topicColors", &meta.topic_colors), ("authorColors", &meta.author_colors)] {
+                for (key, color) in colors.iter().flatten() {
+                    if !USEFUL_COLORS.contains(&color.as_str()) {
+                        problems.push(Problem { source, location: locate_key(&s.text, &[key], Option::Some(color), 0), message: format!("setting.meta.{}: color '{}' of {} is invalid.", field, color, key) })
+                    }
+                }
+            }
+        }
+        self.problems.append(&mut problems);
+    }
+
+    /// 以apply时相同的方式合并所有文件，报告被重复声明的设置部分。合并会取走已解析的文件，因此放在最后执行。
+    fn check_reduce(&mut self) {
+        let mut indexes: Vec<usize> = Vec::new();
+        let mut files: Vec<ApplyFile> = Vec::new();
+        for (source, s) in self.sources.iter_mut().enumerate() {
+            if let Some(f) = s.file.take() {
+                indexes.push(source);
+                files.push(f);
+            }
+        }
+        if let Err(e) = reduce_apply_files(files) {
+            for section in e.sections {
+                let sources: Vec<usize> = section.files.iter().map(|i| indexes[*i]).collect();
+                let locations: Vec<Option<Location>> = sources.iter().map(|s| locate_key(&self.sources[*s].text, section.keys, Option::None, 0)).collect();
+                for i in 1..sources.len() {
+                    let message = format!("setting.{} is already declared at {}.", section.name, self.display_location(sources[0], locations[0]));
+                    self.problems.push(Problem { source: sources[i], location: locations[i], message });
+                }
+            }
+        }
+    }
+
+    fn check_tags(&mut self) {
+        let mut names = std::mem::take(&mut self.names);
+        let mut entries = Vec::new();
+        for (source, s) in self.sources.iter().enumerate() {
+            if let Some(tags) = s.file.as_ref().and_then(|f| f.tags.as_ref()) {
+                expand_tags(tags.iter().collect(), source, Option::None, &mut names, &mut entries);
+            }
+        }
+
+        let mut problems = self.check_duplicates("Tag", &entries);
+        for e in entries.iter() {
+            let name = &e.form.name;
+            if let Some(ref color) = e.form.color {
+                if !e.root {
+                    problems.push(Problem { source: e.source, location: self.locate(e, name), message: format!("Tag '{}' is not a root tag, so it cannot have a color.", e.address) })
+                }else if !USEFUL_COLORS.contains(&color.as_str()) {
+                    problems.push(Problem { source: e.source, location: self.locate(e, name), message: format!("Tag '{}' has an invalid color '{}'.", e.address, color) })
+                }
+            }
+            //link在所有rename完成后才被解析，因此指向rename之前地址的link将找不到目标
+            for link in e.form.links.iter().flatten() {
+                if entries.iter().any(|t| address_ends_with(&t.final_address, link)) { continue }
+                if let Some(t) = entries.iter().find(|t| t.rename.is_some() && address_ends_with(&t.address, link)) {
+                    problems.push(Problem { source: e.source, location: self.locate(e, name), message: format!("Tag '{}' links to '{}', but it is renamed to '{}' at {}.", e.address, link, t.final_address, self.display_location(t.source, self.locate(t, &t.form.name))) })
+                }
+            }
+        }
+        self.problems.append(&mut problems);
+        self.names = names;
+    }
+
+    fn check_topics(&mut self) {
+        let mut names = std::mem::take(&mut self.names);
+        let mut entries = Vec::new();
+        for (source, s) in self.sources.iter().enumerate() {
+            if let Some(topics) = s.file.as_ref().and_then(|f| f.topics.as_ref()) {
+                expand_topics(topics.iter().collect(), source, Option::None, &mut names, &mut entries);
+            }
+        }
+        let mut problems = self.check_duplicates("Topic", &entries);
+        self.problems.append(&mut problems);
+        self.names = names;
+    }
+
+    fn check_authors(&mut self) {
+        let mut names = std::mem::take(&mut self.names);
+        let mut entries = Vec::new();
+        for (source, s) in self.sources.iter().enumerate() {
+            for form in s.file.as_ref().and_then(|f| f.authors.as_ref()).into_iter().flatten() {
+                let nth = next_name(&mut names, source, &form.name);
+                let final_address = form.rename.clone().unwrap_or_else(|| form.name.clone());
+                entries.push(CheckEntry { source, nth, form, rename: form.rename.as_deref(), address: form.name.clone(), final_address, root: true });
+            }
+        }
+        let mut problems = self.check_duplicates("Author", &entries);
+        self.problems.append(&mut problems);
+        self.names = names;
+    }
+
+    /// 检查重复声明的项，以及rename之后与其他项重名的项。
+    fn check_duplicates<T: NamedForm>(&self, kind: &str, entries: &[CheckEntry<T>]) -> Vec<Problem> {
+        let mut problems = Vec::new();
+        let mut declared: HashMap<&str, &CheckEntry<T>> = HashMap::new();
+        let mut finals: HashMap<&str, &CheckEntry<T>> = HashMap::new();
+        for e in entries {
+            let location = self.locate(e, e.form.name());
+            if let Some(first) = declared.get(e.address.as_str()) {
+                problems.push(Problem { source: e.source, location, message: format!("{} '{}' is already declared at {}.", kind, e.address, self.display_location(first.source, self.locate(first, first.form.name()))) });
+                continue
+            }
+            declared.insert(&e.address, e);
+            match finals.get(e.final_address.as_str()) {
+                Some(first) if e.rename.is_some() => problems.push(Problem { source: e.source, location, message: format!("{} '{}' cannot be renamed to '{}', because it is also declared at {}.", kind, e.address, e.final_address, self.display_location(first.source, self.locate(first, first.form.name()))) }),
+                Some(first) => problems.push(Problem { source: first.source, location: self.locate(first, first.form.name()), message: format!("{} '{}' cannot be renamed to '{}', because it is also declared at {}.", kind, first.address, first.final_address, self.display_location(e.source, location)) }),
+                None => { finals.insert(&e.final_address, e); }
+            }
+        }
+        problems
+    }
+
+    /// 打印所有问题。没有问题时返回true。
+    fn report(&mut self) -> bool {
+        self.problems.sort_by_key(|p| (p.source, p.location));
+        for p in self.problems.iter() {
+            println!("\x1b[31m{}\x1b[0m: {}", self.display_location(p.source, p.location), p.message);
+        }
+        if self.problems.is_empty() {
+            println!("{} files checked, no problems found.", self.sources.len());
+        }else{
+            let mut files: Vec<usize> = self.problems.iter().map(|p| p.source).collect();
+            files.dedup();
+            println!("---");
+            println!("{} problems found in {} of {} files.", self.problems.len(), files.len(), self.sources.len());
+        }
+        self.problems.is_empty()
+    }
+}
+
+trait NamedForm {
+    fn name(&self) -> &str;
+}
+
+impl NamedForm for TagBulkForm {
+    fn name(&self) -> &str { &self.name }
+}
+
+impl NamedForm for TopicBulkForm {
+    fn name(&self) -> &str { &self.name }
+}
+
+impl NamedForm for AuthorBulkForm {
+    fn name(&self) -> &str { &self.name }
+}
+
+fn next_name(names: &mut HashMap<(usize, String), usize>, source: usize, name: &str) -> usize {
+    let count = names.entry((source, name.to_string())).or_insert(0);
+    *count += 1;
+    *count - 1
+}
+
+/// 按服务器定位标签的方式展开标签树。子标签的地址以父标签rename之后的名称为准。
+fn expand_tags<'a>(forms: Vec<&'a TagBulkForm>, source: usize, parent: Option<&str>, names: &mut HashMap<(usize, String), usize>, entries: &mut Vec<CheckEntry<'a, TagBulkForm>>) {
+    for form in forms {
+        let nth = next_name(names, source, &form.name);
+        let (address, final_address) = match parent {
+            Some(p) => (format!("{}.{}", p, form.name), format!("{}.{}", p, form.rename.as_ref().unwrap_or(&form.name))),
+            None => (form.name.clone(), form.rename.clone().unwrap_or_else(|| form.name.clone()))
+        };
+        entries.push(CheckEntry { source, nth, form, rename: form.rename.as_deref(), address, final_address: final_address.clone(), root: parent.is_none() });
+        if let Some(ref children) = form.children {
+            expand_tags(children.iter().map(|c| c.as_ref()).collect(), source, Option::Some(&final_address), names, entries);
+        }
+    }
+}
+
+fn expand_topics<'a>(forms: Vec<&'a TopicBulkForm>, source: usize, parent: Option<&str>, names: &mut HashMap<(usize, String), usize>, entries: &mut Vec<CheckEntry<'a, TopicBulkForm>>) {
+    for form in forms {
+        let nth = next_name(names, source, &form.name);
+        let (address, final_address) = match parent {
+            Some(p) => (format!("{}/{}", p, form.name), format!("{}/{}", p, form.rename.as_ref().unwrap_or(&form.name))),
+            None => (form.name.clone(), form.rename.clone().unwrap_or_else(|| form.name.clone()))
+        };
+        entries.push(CheckEntry { source, nth, form, rename: form.rename.as_deref(), address, final_address: final_address.clone(), root: parent.is_none() });
+        if let Some(ref children) = form.children {
+            expand_topics(children.iter().map(|c| c.as_ref()).collect(), source, Option::Some(&final_address), names, entries);
+        }
+    }
+}
+
+/// 判断地址是否以link结尾。与服务器解析link的方式相同，link可以只给出地址的末尾几段。
+fn address_ends_with(address: &str, link: &str) -> bool {
+    let address: Vec<&str> = address.split('.').map(str::trim).collect();
+    let link: Vec<&str> = link.split('.').map(str::trim).collect();
+    address.ends_with(&link)
+}
+
+/// 解析一个apply文件。出错时给出错误的位置和去掉位置后的错误信息。
+fn parse_apply_file(text: &str, extension: &str) -> Result<ApplyFile, ParseError> {
+    match extension {
+        "json" => serde_json::from_str(text).map_err(|e| {
+            let (line, column) = (e.line(), e.column());
+            (Option::Some((line, column)), strip_location(e.to_string(), line, column))
+        }),
+        "yaml" => serde_yaml::from_str(text).map_err(|e| match e.location() {
+            Some(l) => (Option::Some((l.line(), l.column())), strip_location(e.to_string(), l.line(), l.column())),
+            None => (Option::None, e.to_string())
+        }),
+        "toml" => toml::from_str(text).map_err(|e| (e.span().map(|s| offset_to_location(text, s.start)), e.message().to_string())),
+        _ => Result::Err((Option::None, format!("Unsupported file type {}.", extension)))
+    }
+}
+
+/// 将apply文件解析为通用的值。文件本身的语法有错误时返回None。
+fn parse_apply_value(text: &str, extension: &str) -> Option<Value> {
+    match extension {
+        "json" => serde_json::from_str(text).ok(),
+        "yaml" => serde_yaml::from_str(text).ok(),
+        "toml" => toml::from_str(text).ok(),
+        _ => Option::None
+    }
+}
+
+/// 逐个字段、逐项地解析apply文件，跳过有错误的项并报告每一个错误。
+fn parse_apply_entries(text: &str, value: Value) -> (ApplyFile, Vec<ParseError>) {
+    let mut file = ApplyFile::default();
+    let mut errors = Vec::new();
+    let Value::Object(map) = value else {
+        errors.push((Option::None, "apply file must be an object.".to_string()));
+        return (file, errors)
+    };
+    for (key, value) in map {
+        match key.as_str() {
+            "source_data" | "sourceData" => file.source_data = parse_apply_items(text, &key, value, &mut errors),
+            "tags" => file.tags = parse_apply_items(text, &key, value, &mut errors),
+            "topics" => file.topics = parse_apply_items(text, &key, value, &mut errors),
+            "authors" => file.authors = parse_apply_items(text, &key, value, &mut errors),
+            "setting" => file.setting = parse_apply_setting(text, value, &mut errors),
+            _ => if let Err(e) = serde_json::from_value::<ApplyFile>(json!({ key.clone(): value })) {
+                errors.push((locate_key(text, &[&key], Option::None, 0), e.to_string()))
+            }
+        }
+    }
+    (file, errors)
+}
+
+/// 逐项解析一个列表字段。有name的项以name定位，否则以字段本身定位。
+fn parse_apply_items<T: DeserializeOwned>(text: &str, key: &str, value: Value, errors: &mut Vec<ParseError>) -> Option<Vec<T>> {
+    let field_location = locate_key(text, &[key], Option::None, 0);
+    let Value::Array(items) = value else {
+        if let Err(e) = serde_json::from_value::<Vec<T>>(value) {
+            errors.push((field_location, format!("{}: {}", key, e)))
+        }
+        return Option::None
+    };
+    let mut names: HashMap<String, usize> = HashMap::new();
+    let mut ret = Vec::with_capacity(items.len());
+    for (i, item) in items.into_iter().enumerate() {
+        let location = match item.get("name").and_then(|n| n.as_str()) {
+            Some(name) => locate_key(text, &["name"], Option::Some(name), names.get(name).copied().unwrap_or(0)),
+            None => field_location
+        };
+        count_names(&item, &mut names);
+        match serde_json::from_value::<T>(item) {
+            Ok(form) => ret.push(form),
+            Err(e) => errors.push((location, format!("{}[{}]: {}", key, i, e)))
+        }
+    }
+    Option::Some(ret)
+}
+
+/// 计数项及其子项中出现的name，以便在文本中找到后续同名项的位置。
+fn count_names(item: &Value, names: &mut HashMap<String, usize>) {
+    if let Some(name) = item.get("name").and_then(|n| n.as_str()) {
+        *names.entry(name.to_string()).or_insert(0) += 1;
+    }
+    for child in item.get("children").and_then(|c| c.as_array()).into_iter().flatten() {
+        count_names(child, names);
+    }
+}
+
+/// 逐个部分解析设置。
+fn parse_apply_setting(text: &str, value: Value, errors: &mut Vec<ParseError>) -> Option<ApplyFileSetting> {
+    let Value::Object(map) = value else {
+        if let Err(e) = serde_json::from_value::<ApplyFileSetting>(value) {
+            errors.push((locate_key(text, &["setting"], Option::None, 0), format!("setting: {}", e)))
+        }
+        return Option::None
+    };
+    let mut ret = ApplyFileSetting::default();
+    for (key, value) in map {
+        match serde_json::from_value::<ApplyFileSetting>(json!({ key.clone(): value })) {
+            Ok(s) => {
+                ret.meta = ret.meta.or(s.meta);
+                ret.query = ret.query.or(s.query);
+                ret.import = ret.import.or(s.import);
+                ret.storage = ret.storage.or(s.storage);
+                ret.server = ret.server.or(s.server);
+                ret.find_similar = ret.find_similar.or(s.find_similar);
+                ret.source_sites = ret.source_sites.or(s.source_sites);
+            },
+            Err(e) => errors.push((locate_key(text, &[&key], Option::None, 0), format!("setting.{}: {}", key, e)))
+        }
+    }
+    Option::Some(ret)
+}
+
+fn strip_location(message: String, line: usize, column: usize) -> String {
+    match message.strip_suffix(&format!(" at line {} column {}", line, column)) {
+        Some(m) => m.to_string(),
+        None => message
+    }
+}
+
 fn read_input(input: &Vec<ApplyInputType>) -> Result<ApplyFile, Box<dyn Error>> {
     let mut files: Vec<ApplyFile> = Vec::new();
     for i in input {
         match i {
             ApplyInputType::Directory(d) => files.append(&mut read_from_directory(d)?),
-            ApplyInputType::File(f) => files.push(read_from_file(f)?),
+            ApplyInputType::File(f) => files.push(read_from_file(f).map_err(|e| file_error(f, e))?),
             ApplyInputType::Input => files.push(read_from_input()?)
         }
     }
@@ -153,24 +1058,39 @@ fn read_input(input: &Vec<ApplyInputType>) -> Result<ApplyFile, Box<dyn Error>>
 
 fn read_from_directory(d: &PathBuf) -> Result<Vec<ApplyFile>, Box<dyn Error>> {
     let mut files: Vec<ApplyFile> = Vec::new();
+    for f in list_directory(d)? {
+        files.push(read_from_file(&f).map_err(|e| file_error(&f, e))?)
+    }
+    Result::Ok(files)
+}
+
+/// 列出目录下所有可被apply读取的文件，按文件名排序。
+fn list_directory(d: &PathBuf) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut files: Vec<PathBuf> = Vec::new();
     for item in std::fs::read_dir(d)? {
         if let Ok(entry) = item {
             if entry.file_type()?.is_file() {
                 let file_name = entry.file_name();
-                let extension_str = PathBuf::from(&file_name).extension().unwrap().to_str().unwrap().to_lowercase();
+                let extension_str = PathBuf::from(&file_name).extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
                 let extension = extension_str.as_str();
                 if extension == "json" || extension == "yaml" || extension == "toml" {
                     let mut f = d.clone();
                     f.push(&file_name);
-                    files.push(read_from_file(&f)?)
+                    files.push(f)
                 }
             }
         }
     }
+    files.sort();
     Result::Ok(files)
 }
 
-fn read_from_file(f: &PathBuf) -> Result<ApplyFile, Box<dyn Error>> {
+/// 为读取错误加上文件名，否则从目录读取时无法知道是哪个文件出错。
+fn file_error(f: &Path, e: Box<dyn Error>) -> Box<dyn Error> {
+    Box::new(ApplicationError::new(&format!("{}: {}", f.to_str().unwrap(), e)))
+}
+
+pub fn read_from_file(f: &PathBuf) -> Result<ApplyFile, Box<dyn Error>> {
     let text = std::fs::read_to_string(f)?;
     let extension_str = f.extension().unwrap().to_str().unwrap().to_lowercase();
     let extension = extension_str.as_str();
@@ -191,6 +1111,20 @@ fn read_from_file(f: &PathBuf) -> Result<ApplyFile, Box<dyn Error>> {
     }
 }
 
+/// 将内容写入apply文件，根据扩展名选择格式。写出的文件可以直接被apply读取。
+pub fn write_to_file(f: &PathBuf, content: &ApplyFile) -> Result<(), Box<dyn Error>> {
+    let extension_str = f.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    let extension = extension_str.as_str();
+    let text = match extension {
+        "json" => serde_json::to_string_pretty(content)?,
+        "yaml" => serde_yaml::to_string(content)?,
+        "toml" => toml::to_string(content)?,
+        _ => return Result::Err(Box::new(ApplicationError::new(&format!("Unsupported file type {}.", extension))))
+    };
+    std::fs::write(f, text)?;
+    Result::Ok(())
+}
+
 fn read_from_input() -> Result<ApplyFile, Box<dyn Error>> {
     let lines: Vec<String> = stdin().lines().map(|f| f.unwrap()).collect();
     let stdin = lines.join("\n");
@@ -212,14 +1146,14 @@ fn read_from_input() -> Result<ApplyFile, Box<dyn Error>> {
     }
 }
 
-fn reduce_apply_files(mut files: Vec<ApplyFile>) -> Result<ApplyFile, Box<dyn Error>> {
+fn reduce_apply_files(files: Vec<ApplyFile>) -> Result<ApplyFile, ReduceError> {
     let mut ret_source_data: Vec<SourceDataBulkForm> = Vec::new(); 
     let mut ret_tags: Vec<TagBulkForm> = Vec::new(); 
     let mut ret_topics: Vec<TopicBulkForm> = Vec::new(); 
     let mut ret_authors: Vec<AuthorBulkForm> = Vec::new();
-    let mut ret_settings: Vec<ApplyFileSetting> = Vec::new();
+    let mut ret_settings: Vec<(usize, ApplyFileSetting)> = Vec::new();
 
-    while let Some(f) = files.pop() {
+    for (i, f) in files.into_iter().enumerate().rev() {
         if let Some(mut source_data) = f.source_data {
             ret_source_data.append(&mut source_data)
         }
@@ -233,7 +1167,7 @@ fn reduce_apply_files(mut files: Vec<ApplyFile>) -> Result<ApplyFile, Box<dyn Er
             ret_authors.append(&mut authors)
         }
         if let Some(setting) = f.setting {
-            ret_settings.push(setting)
+            ret_settings.push((i, setting))
         }
     }
 
@@ -246,7 +1180,8 @@ fn reduce_apply_files(mut files: Vec<ApplyFile>) -> Result<ApplyFile, Box<dyn Er
     })
 }
 
-fn reduce_apply_setting(settings: Vec<ApplyFileSetting>) -> Result<Option<ApplyFileSetting>, Box<dyn Error>> {
+/// 合并各文件的设置。每个部分只能在一个文件中声明，所有被重复声明的部分都会在错误中给出。
+fn reduce_apply_setting(settings: Vec<(usize, ApplyFileSetting)>) -> Result<Option<ApplyFileSetting>, ReduceError> {
     let mut ret = ApplyFileSetting {
         meta: Option::None,
         query: Option::None,
@@ -256,84 +1191,172 @@ fn reduce_apply_setting(settings: Vec<ApplyFileSetting>) -> Result<Option<ApplyF
         find_similar: Option::None,
         source_sites: Option::None
     };
+    let mut sections = [
+        DuplicatedSection { name: "server", keys: &["server"], files: Vec::new() },
+        DuplicatedSection { name: "storage", keys: &["storage"], files: Vec::new() },
+        DuplicatedSection { name: "query", keys: &["query"], files: Vec::new() },
+        DuplicatedSection { name: "meta", keys: &["meta"], files: Vec::new() },
+        DuplicatedSection { name: "import", keys: &["import"], files: Vec::new() },
+        DuplicatedSection { name: "find_similar", keys: &["find_similar", "findSimilar"], files: Vec::new() },
+        DuplicatedSection { name: "source_sites", keys: &["source_sites", "sites", "sourceSites"], files: Vec::new() }
+    ];
 
-    
-    for setting in settings {
-        if let Some(server) = setting.server {
-            if ret.server.is_none() {
-                ret.server = Option::Some(server)
-            }else{
-                return Result::Err(Box::new(ApplicationError::new("setting.server is declared multiple times.")))
-            }
-        }
-        if let Some(storage) = setting.storage {
-            if ret.storage.is_none() {
-                ret.storage = Option::Some(storage)
-            }else{
-                return Result::Err(Box::new(ApplicationError::new("setting.storage is declared multiple times.")))
-            }
-        }
-        if let Some(query) = setting.query {
-            if ret.query.is_none() {
-                ret.query = Option::Some(query)
-            }else{
-                return Result::Err(Box::new(ApplicationError::new("setting.query is declared multiple times.")))
-            }
-        }
-        if let Some(meta) = setting.meta {
-            if ret.meta.is_none() {
-                ret.meta = Option::Some(meta)
-            }else{
-                return Result::Err(Box::new(ApplicationError::new("setting.meta is declared multiple times.")))
-            }
-        }
-        if let Some(import) = setting.import {
-            if ret.import.is_none() {
-                ret.import = Option::Some(import)
-            }else{
-                return Result::Err(Box::new(ApplicationError::new("setting.import is declared multiple times.")))
-            }
-        }
-        if let Some(find_similar) = setting.find_similar {
-            if ret.find_similar.is_none() {
-                ret.find_similar = Option::Some(find_similar)
-            }else{
-                return Result::Err(Box::new(ApplicationError::new("setting.find_similar is declared multiple times.")))
-            }
-        }
-        if let Some(sites) = setting.source_sites {
-            if ret.source_sites.is_none() {
-                ret.source_sites = Option::Some(sites)
-            }else{
-                return Result::Err(Box::new(ApplicationError::new("setting.source_sites is declared multiple times.")))
-            }
-        }
+    for (i, setting) in settings {
+        reduce_section(&mut ret.server, setting.server, &mut sections[0], i);
+        reduce_section(&mut ret.storage, setting.storage, &mut sections[1], i);
+        reduce_section(&mut ret.query, setting.query, &mut sections[2], i);
+        reduce_section(&mut ret.meta, setting.meta, &mut sections[3], i);
+        reduce_section(&mut ret.import, setting.import, &mut sections[4], i);
+        reduce_section(&mut ret.find_similar, setting.find_similar, &mut sections[5], i);
+        reduce_section(&mut ret.source_sites, setting.source_sites, &mut sections[6], i);
+    }
+
+    let duplicated: Vec<DuplicatedSection> = sections.into_iter().filter(|s| s.files.len() > 1).map(|mut s| { s.files.sort(); s }).collect();
+    if !duplicated.is_empty() {
+        return Result::Err(ReduceError { sections: duplicated })
     }
 
     Result::Ok(if ret.meta.is_some() || ret.query.is_some() || ret.import.is_some() || ret.server.is_some() || ret.storage.is_some() || ret.find_similar.is_some() || ret.source_sites.is_some() { Option::Some(ret) }else{ Option::None })
 }
 
-#[derive(Deserialize)]
+fn reduce_section<T>(ret: &mut Option<T>, value: Option<T>, section: &mut DuplicatedSection, file: usize) {
+    if let Some(value) = value {
+        if ret.is_none() {
+            *ret = Option::Some(value)
+        }
+        section.files.push(file)
+    }
+}
+
+/// 被多个文件重复声明的设置部分。keys是此部分在文件中可用的名称，files是声明了它的文件序号。
+#[derive(Debug)]
+struct DuplicatedSection {
+    name: &'static str,
+    keys: &'static [&'static str],
+    files: Vec<usize>
+}
+
+#[derive(Debug)]
+struct ReduceError {
+    sections: Vec<DuplicatedSection>
+}
+
+impl std::fmt::Display for ReduceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let messages: Vec<String> = self.sections.iter().map(|s| format!("setting.{} is declared multiple times.", s.name)).collect();
+        write!(f, "{}", messages.join(" "))
+    }
+}
+
+impl Error for ReduceError {}
+
+#[derive(Serialize, Deserialize, Default, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct ApplyFile {
-    #[serde(alias = "source_data", alias = "sourceData")]
+    #[serde(alias = "source_data", alias = "sourceData", skip_serializing_if = "Option::is_none")]
     pub source_data: Option<Vec<SourceDataBulkForm>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub tags: Option<Vec<TagBulkForm>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub topics: Option<Vec<TopicBulkForm>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub authors: Option<Vec<AuthorBulkForm>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub setting: Option<ApplyFileSetting>
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize, Default, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct ApplyFileSetting {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub meta: Option<MetaOptionUpdateForm>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub query: Option<QueryOptionUpdateForm>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub import: Option<ImportOptionUpdateForm>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub storage: Option<StorageOptionUpdateForm>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub server: Option<ServerOptionUpdateForm>,
-    #[serde(alias = "find_similar", alias = "findSimilar")]
+    #[serde(alias = "find_similar", alias = "findSimilar", skip_serializing_if = "Option::is_none")]
     pub find_similar: Option<FindSimilarOptionUpdateForm>,
-    #[serde(alias = "source_sites", alias = "sites", alias = "sourceSites")]
+    #[serde(alias = "source_sites", alias = "sites", alias = "sourceSites", skip_serializing_if = "Option::is_none")]
     pub source_sites: Option<Vec<SourceSiteUpdateForm>>
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schema_aliases_cover_all_structs() {
+        let (root, added) = apply_file_schema();
+        let missing: Vec<&String> = root.definitions.iter().filter(|(name, schema)| {
+            let is_struct = matches!(schema, Schema::Object(o) if o.object.as_ref().is_some_and(|o| !o.properties.is_empty()));
+            is_struct && !added.contains(name)
+        }).map(|(name, _)| name).collect();
+        assert!(missing.is_empty(), "definitions without aliases: {:?}", missing);
+    }
+
+    fn checker(files: &[(&str, &str)]) -> Checker {
+        let mut checker = Checker { sources: Vec::new(), problems: Vec::new(), names: HashMap::new() };
+        for (name, text) in files {
+            let extension = name.rsplit('.').next().unwrap();
+            checker.add_text(name.to_string(), text.to_string(), extension);
+        }
+        checker
+    }
+
+    fn messages(checker: &Checker) -> Vec<(String, String)> {
+        let mut messages: Vec<(String, String)> = checker.problems.iter().map(|p| (checker.display_location(p.source, p.location), p.message.clone())).collect();
+        messages.sort();
+        messages
+    }
+
+    #[test]
+    fn check_duplicated_and_renamed_authors() {
+        let mut checker = checker(&[
+            ("a.yaml", "authors:\n  - name: x\n  - name: y\n    rename: z\n"),
+            ("b.json", "{\"authors\": [{\"name\": \"x\"}, {\"name\": \"z\"}]}")
+        ]);
+        checker.check_authors();
+        assert_eq!(messages(&checker), vec![
+            ("a.yaml:3:5".to_string(), "Author 'y' cannot be renamed to 'z', because it is also declared at b.json:1:31.".to_string()),
+            ("b.json:1:16".to_string(), "Author 'x' is already declared at a.yaml:2:5.".to_string())
+        ]);
+    }
+
+    #[test]
+    fn check_duplicated_tags_in_tree() {
+        let mut checker = checker(&[("a.toml", "[[tags]]\nname = \"a\"\n[[tags.children]]\nname = \"b\"\n[[tags]]\nname = \"b\"\n[[tags]]\nname = \"a\"\n")]);
+        checker.check_tags();
+        //a.b与b的地址不同，不算重复
+        assert_eq!(messages(&checker), vec![("a.toml:8:1".to_string(), "Tag 'a' is already declared at a.toml:2:1.".to_string())]);
+    }
+
+    #[test]
+    fn invalid_entries_do_not_hide_others() {
+        let mut checker = checker(&[
+            ("a.yaml", "tags:\n  - name: a\n    colour: red\n  - name: b\n    color: purple\n    children:\n      - name: c\n        color: red\nsetting:\n  server:\n    bogus: 1\n  meta:\n    autoCleanTagme: true\n"),
+            ("b.yaml", "tags:\n  - name: b\nsetting:\n  meta:\n    autoCleanTagme: false\n")
+        ]);
+        checker.check_tags();
+        checker.check_reduce();
+        let messages = messages(&checker);
+        assert_eq!(messages.iter().map(|(l, m)| format!("{} {}", l, m.split(',').next().unwrap())).collect::<Vec<_>>(), vec![
+            "a.yaml:10:3 setting.server: unknown field `bogus`",
+            "a.yaml:2:5 tags[0]: unknown field `colour`",
+            "a.yaml:4:5 Tag 'b' has an invalid color 'purple'.",
+            "a.yaml:7:9 Tag 'b.c' is not a root tag",
+            "b.yaml:2:5 Tag 'b' is already declared at a.yaml:4:5.",
+            "b.yaml:4:3 setting.meta is already declared at a.yaml:12:3."
+        ]);
+    }
+
+    #[test]
+    fn syntax_error_reported_once() {
+        let checker = checker(&[("a.json", "{\"tags\": [}")]);
+        assert_eq!(checker.problems.len(), 1);
+        assert!(checker.sources[0].file.is_none());
+    }
+}
@@ -0,0 +1,65 @@
+use crate::module::connect::{ConnectModule, SelectorCheck};
+use super::Context;
+
+
+/// 对查询的前若干行执行connect parser中的每个选择器，按选择器报告成功与失败的次数，以及第一条失败的原因。
+pub fn check_connect(context: &Context<'_>, split: &[String], rows: u32, vars: &[(String, String)]) {
+    let connect_module = ConnectModule::new(&context.config);
+    let mut conn = match connect_module.connect() {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Error occrred in connecting. {}", e);
+            return
+        }
+    };
+
+    let mut checks: Vec<SelectorCheck> = Vec::new();
+    let mut index: u32 = 0;
+    let mut failed_rows: u32 = 0;
+    for s in split {
+        if index >= rows { break }
+        let mut stat = match conn.statement(std::slice::from_ref(s), Option::Some(rows - index), vars, Option::None) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("Error occrred in statement preparing. {}", e);
+                return
+            }
+        };
+        while let Some(r) = stat.check_next(&mut checks) {
+            index += 1;
+            if let Err(e) = r {
+                failed_rows += 1;
+                println!("Row {:>4} | \x1b[1;31m{}\x1b[0m", index, e);
+            }
+        }
+    }
+    if failed_rows > 0 {
+        println!("---");
+    }
+
+    let field_max_len = checks.iter().map(|c| c.field.len()).max().unwrap_or(10);
+    let selector_max_len = checks.iter().map(|c| c.selector.len()).max().unwrap_or(10);
+    let mut site: Option<&str> = Option::None;
+    for check in checks.iter() {
+        if site != Option::Some(check.site.as_str()) {
+            println!("\x1b[1;33m[{}]\x1b[0m", check.site);
+            site = Option::Some(check.site.as_str());
+        }
+        let prefix = format!("  {:field_max_len$} | {:selector_max_len$} |", check.field, check.selector, field_max_len = field_max_len, selector_max_len = selector_max_len);
+        if let Some(ref e) = check.first_error {
+            println!("{} \x1b[1;31m{} ok, {} failed. {}\x1b[0m", prefix, check.success, check.failed, e);
+        }else{
+            println!("{} \x1b[1;32m{} ok.\x1b[0m", prefix, check.success);
+        }
+    }
+
+    let failed_selectors = checks.iter().filter(|c| c.failed > 0).count();
+    if !checks.is_empty() {
+        println!("---");
+    }
+    if failed_rows > 0 || failed_selectors > 0 {
+        println!("Checked {} row(s). \x1b[1;31m{}\x1b[0m row(s) cannot be processed, \x1b[1;31m{}\x1b[0m selector(s) failed.", index, failed_rows, failed_selectors);
+    }else{
+        println!("Checked {} row(s). All selectors passed.", index);
+    }
+}
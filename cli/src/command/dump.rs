@@ -0,0 +1,205 @@
+use std::{collections::HashMap, error::Error, io::Write, path::{Path, PathBuf}};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use crate::module::api::{bulk::{AuthorBulkForm, SourceDataBulkForm, TagBulkForm, TopicBulkForm}, meta::{MetaModule, TagDetailRes, TagTreeNode, TopicDetailRes}, setting::SettingModule, source_data::SourceDataModule};
+use crate::module::server::ServerManager;
+use super::{Context, apply::{ApplyFile, ApplyFileSetting, author_detail_to_bulk_form, collect_tag_addresses, list_all_authors, list_all_topics, tag_detail_to_bulk_form, topic_detail_to_bulk_form, write_to_file}, source_data::{detail_to_bulk_form, query_all}};
+
+
+pub struct DumpOptions {
+    pub tags: bool,
+    pub topics: bool,
+    pub authors: bool,
+    pub settings: bool,
+    pub source_data: Option<String>,
+    pub output: PathBuf,
+    pub format: String
+}
+
+/// 读取服务器的当前状态，按部分写入output目录下的apply文件。没有指定任何部分时，导出标签、主题、作者和设置。
+pub async fn dump(context: &mut Context<'_>, options: &DumpOptions) {
+    let DumpOptions { tags, topics, authors, settings, source_data, output, format } = options;
+    let all = !tags && !topics && !authors && !settings && source_data.is_none();
+
+    if let Err(e) = context.server_manager.waiting_for_start().await {
+        eprintln!("Cannot establish connection to server. {}", e);
+        return
+    }
+    if let Err(e) = std::fs::create_dir_all(output) {
+        eprintln!("Cannot create directory {}. {}", output.to_str().unwrap(), e);
+        return
+    }
+
+    if *tags || all {
+        match dump_tags(context.server_manager).await {
+            Err(e) => eprintln!("Error occrred in requesting. {}", e),
+            Ok(r) => write_section(output, "tags", format, r.len(), &ApplyFile { tags: Option::Some(r), ..Default::default() })
+        }
+    }
+    if *topics || all {
+        match dump_topics(context.server_manager).await {
+            Err(e) => eprintln!("Error occrred in requesting. {}", e),
+            Ok(r) => write_section(output, "topics", format, r.len(), &ApplyFile { topics: Option::Some(r), ..Default::default() })
+        }
+    }
+    if *authors || all {
+        match dump_authors(context.server_manager).await {
+            Err(e) => eprintln!("Error occrred in requesting. {}", e),
+            Ok(r) => write_section(output, "authors", format, r.len(), &ApplyFile { authors: Option::Some(r), ..Default::default() })
+        }
+    }
+    if *settings || all {
+        match dump_setting(context.server_manager).await {
+            Err(e) => eprintln!("Error occrred in requesting. {}", e),
+            Ok(r) => write_section(output, "setting", format, 1, &ApplyFile { setting: Option::Some(r), ..Default::default() })
+        }
+    }
+    if let Some(hql) = source_data {
+        match dump_source_data(context.server_manager, hql).await {
+            Err(e) => eprintln!("Error occrred in requesting. {}", e),
+            Ok(r) => write_section(output, "source_data", format, r.len(), &ApplyFile { source_data: Option::Some(r), ..Default::default() })
+        }
+    }
+}
+
+fn write_section(output: &Path, name: &str, format: &str, count: usize, content: &ApplyFile) {
+    let f = output.join(format!("{}.{}", name, format));
+    match write_to_file(&f, content) {
+        Err(e) => eprintln!("Cannot write to file {}. {}", f.to_str().unwrap(), e),
+        Ok(_) => println!("Total {} {} dumped to {}.", count, name, f.to_str().unwrap())
+    }
+}
+
+fn print_progress(name: &str, index: usize, total: usize) {
+    print!("\rDumping {} {}/{}...", name, index, total);
+    std::io::stdout().flush().unwrap();
+    if index == total {
+        println!();
+    }
+}
+
+async fn dump_tags(server_manager: &ServerManager) -> Result<Vec<TagBulkForm>, Box<dyn Error>> {
+    let mut meta_module = MetaModule::new(server_manager);
+    let tree = meta_module.tag_tree().await?;
+    let mut addresses: HashMap<i32, String> = HashMap::new();
+    collect_tag_addresses(&tree, Option::None, &mut addresses);
+
+    let mut details: HashMap<i32, TagDetailRes> = HashMap::with_capacity(addresses.len());
+    for (i, id) in addresses.keys().enumerate() {
+        details.insert(*id, meta_module.get_tag(*id).await?);
+        print_progress("tags", i + 1, addresses.len());
+    }
+
+    Result::Ok(build_tag_forms(&tree, &mut details, &addresses, true))
+}
+
+/// 按标签树的结构组装表单。同级标签的顺序即为其ordinal。
+fn build_tag_forms(nodes: &[TagTreeNode], details: &mut HashMap<i32, TagDetailRes>, addresses: &HashMap<i32, String>, root: bool) -> Vec<TagBulkForm> {
+    nodes.iter().filter_map(|node| {
+        let detail = details.remove(&node.id)?;
+        let mut form = tag_detail_to_bulk_form(detail, addresses);
+        //子标签的颜色继承自根标签，且不允许单独指定
+        if !root {
+            form.color = Option::None;
+        }
+        if let Some(ref children) = node.children {
+            if !children.is_empty() {
+                form.children = Option::Some(build_tag_forms(children, details, addresses, false).into_iter().map(Box::new).collect());
+            }
+        }
+        Option::Some(form)
+    }).collect()
+}
+
+async fn dump_topics(server_manager: &ServerManager) -> Result<Vec<TopicBulkForm>, Box<dyn Error>> {
+    let mut meta_module = MetaModule::new(server_manager);
+    let records = list_all_topics(&mut meta_module).await?;
+
+    let mut details: HashMap<i32, TopicDetailRes> = HashMap::with_capacity(records.len());
+    for (i, record) in records.iter().enumerate() {
+        details.insert(record.id, meta_module.get_topic(record.id).await?);
+        print_progress("topics", i + 1, records.len());
+    }
+
+    let roots: Vec<i32> = records.iter().filter(|r| r.parent_id.is_none()).map(|r| r.id).collect();
+    Result::Ok(build_topic_forms(&roots, &mut details))
+}
+
+/// 按父子关系组装主题表单。子主题的顺序取自详情中的children列表。
+fn build_topic_forms(ids: &[i32], details: &mut HashMap<i32, TopicDetailRes>) -> Vec<TopicBulkForm> {
+    ids.iter().filter_map(|id| {
+        let mut detail = details.remove(id)?;
+        let children: Vec<i32> = detail.children.take().map(|c| c.iter().map(|c| c.id).collect()).unwrap_or_default();
+        let mut form = topic_detail_to_bulk_form(detail);
+        if !children.is_empty() {
+            form.children = Option::Some(build_topic_forms(&children, details).into_iter().map(Box::new).collect());
+        }
+        Option::Some(form)
+    }).collect()
+}
+
+async fn dump_authors(server_manager: &ServerManager) -> Result<Vec<AuthorBulkForm>, Box<dyn Error>> {
+    let mut meta_module = MetaModule::new(server_manager);
+    let records = list_all_authors(&mut meta_module).await?;
+
+    let mut forms = Vec::with_capacity(records.len());
+    for (i, record) in records.iter().enumerate() {
+        forms.push(author_detail_to_bulk_form(meta_module.get_author(record.id).await?));
+        print_progress("authors", i + 1, records.len());
+    }
+    Result::Ok(forms)
+}
+
+async fn dump_setting(server_manager: &ServerManager) -> Result<ApplyFileSetting, Box<dyn Error>> {
+    let mut setting_module = SettingModule::new(server_manager);
+
+    let mut server = setting_module.get_option("server").await?;
+    //token是访问凭证，不应被写入文件
+    if let Value::Object(ref mut o) = server {
+        o.remove("token");
+    }
+    //内建站点不允许修改任何属性，因此只保留名称
+    let sites: Vec<Value> = setting_module.get_source_sites_value().await?.into_iter().map(|mut site| {
+        if site.get("isBuiltin").and_then(|b| b.as_bool()).unwrap_or(false) {
+            json!({ "name": site["name"] })
+        }else{
+            if let Value::Object(ref mut o) = site {
+                o.remove("isBuiltin");
+            }
+            site
+        }
+    }).collect();
+
+    Result::Ok(ApplyFileSetting {
+        server: to_form("server", server),
+        storage: to_form("storage", setting_module.get_option("storage").await?),
+        meta: to_form("meta", setting_module.get_option("meta").await?),
+        query: to_form("query", setting_module.get_option("query").await?),
+        import: to_form("import", setting_module.get_option("import").await?),
+        find_similar: to_form("find_similar", setting_module.get_option("find-similar").await?),
+        source_sites: to_form("source_sites", Value::Array(sites))
+    })
+}
+
+/// 将服务器返回的设置转换为表单。无法转换时跳过此部分，不影响其他部分的导出。
+fn to_form<T: DeserializeOwned>(name: &str, value: Value) -> Option<T> {
+    match serde_json::from_value(value) {
+        Ok(f) => Option::Some(f),
+        Err(e) => {
+            eprintln!("Setting {} cannot be dumped. {}", name, e);
+            Option::None
+        }
+    }
+}
+
+async fn dump_source_data(server_manager: &ServerManager, hql: &str) -> Result<Vec<SourceDataBulkForm>, Box<dyn Error>> {
+    let mut source_data_module = SourceDataModule::new(server_manager);
+    let items = query_all(&mut source_data_module, Option::Some(hql), Option::None).await?;
+
+    let mut forms = Vec::with_capacity(items.len());
+    for (i, item) in items.iter().enumerate() {
+        forms.push(detail_to_bulk_form(source_data_module.get(&item.site, &item.source_id).await?));
+        print_progress("source data", i + 1, items.len());
+    }
+    Result::Ok(forms)
+}
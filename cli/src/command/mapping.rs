@@ -0,0 +1,313 @@
+use std::{collections::HashMap, error::Error, io::Write, path::PathBuf};
+use serde::Serialize;
+use crate::{module::api::{bulk::{AuthorBulkForm, MappingSourceTagForm, TagBulkForm, TopicBulkForm}, setting::SettingModule, source_data::{SourceDataModule, SourceTagDto}, source_tag_mapping::{SourceMappingTargetItem, SourceMappingTargetItemDetail, SourceTagMappingModule, SourceTagPath}}, utils::error::ApplicationError};
+use super::{Context, apply::{ApplyFile, write_to_file}, source_data::query_all};
+
+
+pub async fn get(context: &mut Context<'_>, site: &str, tag_type: &str, code: &str) {
+    if let Err(e) = context.server_manager.waiting_for_start().await {
+        eprintln!("Cannot establish connection to server. {}", e);
+        return
+    }
+
+    let mut mapping_module = SourceTagMappingModule::new(context.server_manager);
+    match mapping_module.get(site, tag_type, code).await {
+        Err(e) => eprintln!("Error occrred in requesting. {}", e),
+        Ok(r) => if r.is_empty() {
+            println!("No mapping.");
+        }else{
+            for item in &r {
+                println!("- {}", format_target(item));
+            }
+        }
+    }
+}
+
+pub async fn set(context: &mut Context<'_>, site: &str, tag_type: &str, code: &str, targets: &Vec<String>) {
+    let mut items = Vec::with_capacity(targets.len());
+    for target in targets {
+        match parse_target(target) {
+            Err(e) => {
+                eprintln!("{}", e);
+                return
+            },
+            Ok(item) => items.push(item)
+        }
+    }
+
+    if let Err(e) = context.server_manager.waiting_for_start().await {
+        eprintln!("Cannot establish connection to server. {}", e);
+        return
+    }
+
+    let mut mapping_module = SourceTagMappingModule::new(context.server_manager);
+    match mapping_module.update(site, tag_type, code, &items).await {
+        Err(e) => eprintln!("Error occrred in requesting. {}", e),
+        Ok(_) => println!("Mapping of {}/{}/{} updated.", site, tag_type, code)
+    }
+}
+
+pub async fn delete(context: &mut Context<'_>, site: &str, tag_type: &str, code: &str) {
+    if let Err(e) = context.server_manager.waiting_for_start().await {
+        eprintln!("Cannot establish connection to server. {}", e);
+        return
+    }
+
+    let mut mapping_module = SourceTagMappingModule::new(context.server_manager);
+    match mapping_module.delete(site, tag_type, code).await {
+        Err(e) => eprintln!("Error occrred in requesting. {}", e),
+        Ok(_) => println!("Mapping of {}/{}/{} deleted.", site, tag_type, code)
+    }
+}
+
+pub async fn query(context: &mut Context<'_>, site: &str, tags: &Vec<String>) {
+    let mut paths = Vec::with_capacity(tags.len());
+    for tag in tags {
+        match tag.split_once(':') {
+            Some((tag_type, code)) if !tag_type.is_empty() && !code.is_empty() => paths.push(SourceTagPath { source_site: site.to_string(), source_tag_type: tag_type.to_string(), source_tag_code: code.to_string() }),
+            _ => {
+                eprintln!("Source tag '{}' is not in form of <type>:<code>.", tag);
+                return
+            }
+        }
+    }
+
+    if let Err(e) = context.server_manager.waiting_for_start().await {
+        eprintln!("Cannot establish connection to server. {}", e);
+        return
+    }
+
+    let mut mapping_module = SourceTagMappingModule::new(context.server_manager);
+    match mapping_module.batch_query(&paths).await {
+        Err(e) => eprintln!("Error occrred in requesting. {}", e),
+        Ok(r) => for item in &r {
+            let targets = if item.mappings.is_empty() { "(no mapping)".to_string() }else{ item.mappings.iter().map(format_target).collect::<Vec<_>>().join(", ") };
+            let name = item.source_tag.name.as_ref().map(|n| format!(" ({})", n)).unwrap_or_default();
+            println!("- [{}] {}{} -> {}", item.tag_type, item.code, name, targets);
+        }
+    }
+}
+
+pub async fn export(context: &mut Context<'_>, site: &str, output: Option<&PathBuf>, unmapped: bool) {
+    if let Err(e) = context.server_manager.waiting_for_start().await {
+        eprintln!("Cannot establish connection to server. {}", e);
+        return
+    }
+
+    let source_tags = match collect_source_tags(context, site).await {
+        Err(e) => {
+            eprintln!("Error occrred in requesting. {}", e);
+            return
+        },
+        Ok(r) => r
+    };
+    let mut items = match query_mappings(context, site, source_tags).await {
+        Err(e) => {
+            eprintln!("Error occrred in requesting. {}", e);
+            return
+        },
+        Ok(r) => r
+    };
+    let total = items.len();
+    let unmapped_count = items.iter().filter(|i| i.mappings.is_empty()).count();
+    if unmapped {
+        items.retain(|i| i.mappings.is_empty());
+    }
+
+    if let Some(output) = output {
+        if let Err(e) = write_export_file(output, &items) {
+            eprintln!("Cannot write to file {}. {}", output.to_str().unwrap(), e);
+            return
+        }
+    }else{
+        for item in &items {
+            let targets = if item.mappings.is_empty() { "(no mapping)".to_string() }else{ item.mappings.iter().map(|m| format!("{} {} {}", m.meta_type, m.id, m.name)).collect::<Vec<_>>().join(", ") };
+            println!("- [{}] {} ({} use(s)) -> {}", item.tag_type, item.code, item.count, targets);
+        }
+        if !items.is_empty() {
+            println!("---");
+        }
+    }
+    println!("Total {} source tag(s), {} without mapping.", total, unmapped_count);
+}
+
+pub async fn unmapped(context: &mut Context<'_>, site: &str, min_count: u32, limit: Option<usize>, output: Option<&PathBuf>) {
+    if let Err(e) = context.server_manager.waiting_for_start().await {
+        eprintln!("Cannot establish connection to server. {}", e);
+        return
+    }
+
+    let mut source_tags = match collect_source_tags(context, site).await {
+        Err(e) => {
+            eprintln!("Error occrred in requesting. {}", e);
+            return
+        },
+        Ok(r) => r
+    };
+    source_tags.retain(|(_, count)| *count >= min_count);
+    let mut items = match query_mappings(context, site, source_tags).await {
+        Err(e) => {
+            eprintln!("Error occrred in requesting. {}", e);
+            return
+        },
+        Ok(r) => r
+    };
+    items.retain(|i| i.mappings.is_empty());
+    let total = items.len();
+    if let Some(limit) = limit {
+        items.truncate(limit);
+    }
+
+    for item in &items {
+        let name = item.name.as_ref().map(|n| format!(" ({})", n)).unwrap_or_default();
+        println!("- [{}] {}{} {} use(s)", item.tag_type, item.code, name, item.count);
+    }
+    if !items.is_empty() {
+        println!("---");
+    }
+    println!("Total {} source tag(s) used at least {} time(s) without mapping.", total, min_count);
+
+    if let Some(output) = output {
+        let mut setting_module = SettingModule::new(context.server_manager);
+        let source_site = match setting_module.get_source_site(site).await {
+            Err(e) => {
+                eprintln!("Error occrred in requesting. {}", e);
+                return
+            },
+            Ok(r) => r
+        };
+        let (content, skipped) = generate_stub(site, &source_site.tag_type_mappings, &items);
+        for item in &skipped {
+            eprintln!("Source tag [{}] {} skipped: type {} has no definite meta tag type in site setting.", item.tag_type, item.code, item.tag_type);
+        }
+        if let Err(e) = write_to_file(output, &content) {
+            eprintln!("Cannot write to file {}. {}", output.to_str().unwrap(), e);
+            return
+        }
+        println!("Stub apply file written to {}.", output.to_str().unwrap());
+    }
+}
+
+/// 根据site的tagTypeMappings，为每个来源标签生成一个对应类型的元数据标签桩，并填写mapping_source_tags。
+/// 没有映射类型的来源标签，以及映射到UNKNOWN的来源标签(无法区分topic与author)会被跳过并返回。
+fn generate_stub<'a>(site: &str, tag_type_mappings: &HashMap<String, String>, items: &'a [MappingExportItem]) -> (ApplyFile, Vec<&'a MappingExportItem>) {
+    let mut tags = Vec::new();
+    let mut topics = Vec::new();
+    let mut authors = Vec::new();
+    let mut skipped = Vec::new();
+    for item in items {
+        let name = item.name.clone().unwrap_or_else(|| item.code.clone());
+        let other_names = item.other_name.as_ref().map(|n| vec![n.clone()]);
+        let mapping = Option::Some(vec![MappingSourceTagForm { site: site.to_string(), code: item.code.clone(), name: item.name.clone(), other_name: item.other_name.clone(), tag_type: Option::Some(item.tag_type.clone()) }]);
+        match tag_type_mappings.get(&item.tag_type).map(|t| t.as_str()) {
+            Some("TAG") => tags.push(TagBulkForm { name, rename: Option::None, other_names, tag_type: Option::None, is_sequence_group: Option::None, is_override_group: Option::None, description: Option::None, links: Option::None, color: Option::None, mapping_source_tags: mapping, children: Option::None }),
+            Some(t @ ("COPYRIGHT" | "IP" | "CHARACTER")) => topics.push(TopicBulkForm { name, rename: Option::None, other_names, tag_type: Option::Some(t.to_string()), keywords: Option::None, description: Option::None, favorite: Option::None, score: Option::None, mapping_source_tags: mapping, children: Option::None }),
+            Some(t @ ("ARTIST" | "GROUP" | "SERIES")) => authors.push(AuthorBulkForm { name, rename: Option::None, other_names, tag_type: Option::Some(t.to_string()), keywords: Option::None, description: Option::None, favorite: Option::None, score: Option::None, mapping_source_tags: mapping }),
+            _ => skipped.push(item)
+        }
+    }
+    let content = ApplyFile {
+        source_data: Option::None,
+        tags: if tags.is_empty() { Option::None }else{ Option::Some(tags) },
+        topics: if topics.is_empty() { Option::None }else{ Option::Some(topics) },
+        authors: if authors.is_empty() { Option::None }else{ Option::Some(authors) },
+        setting: Option::None
+    };
+    (content, skipped)
+}
+
+/// 遍历此site的全部来源数据，收集其中出现的所有来源标签及其出现次数，按出现次数倒序排列。
+/// 服务端没有直接列出来源标签的API，因此只能通过来源数据详情收集。
+pub async fn collect_source_tags(context: &mut Context<'_>, site: &str) -> Result<Vec<(SourceTagDto, u32)>, Box<dyn Error>> {
+    let mut source_data_module = SourceDataModule::new(context.server_manager);
+    let items = query_all(&mut source_data_module, Option::None, Option::Some(site)).await?;
+    let mut tags: HashMap<(String, String), (SourceTagDto, u32)> = HashMap::new();
+    for (i, item) in items.iter().enumerate() {
+        if item.tag_count > 0 {
+            let detail = source_data_module.get(&item.site, &item.source_id).await?;
+            for tag in detail.tags {
+                tags.entry((tag.tag_type.clone(), tag.code.clone())).or_insert((tag, 0)).1 += 1;
+            }
+        }
+        eprint!("\rCollecting source tags {}/{}...", i + 1, items.len());
+        std::io::stderr().flush().unwrap();
+    }
+    if !items.is_empty() {
+        eprintln!();
+    }
+    let mut ret: Vec<(SourceTagDto, u32)> = tags.into_values().collect();
+    ret.sort_by(|(a, ac), (b, bc)| bc.cmp(ac).then_with(|| a.tag_type.cmp(&b.tag_type)).then_with(|| a.code.cmp(&b.code)));
+    Result::Ok(ret)
+}
+
+/// 分批查询来源标签的映射。返回的顺序与给出的来源标签顺序一致。
+async fn query_mappings(context: &mut Context<'_>, site: &str, source_tags: Vec<(SourceTagDto, u32)>) -> Result<Vec<MappingExportItem>, Box<dyn Error>> {
+    const BATCH_SIZE: usize = 200;
+    let mut mapping_module = SourceTagMappingModule::new(context.server_manager);
+    let mut ret = Vec::with_capacity(source_tags.len());
+    for chunk in source_tags.chunks(BATCH_SIZE) {
+        let paths = chunk.iter().map(|(t, _)| SourceTagPath { source_site: site.to_string(), source_tag_type: t.tag_type.clone(), source_tag_code: t.code.clone() }).collect();
+        let r = mapping_module.batch_query(&paths).await?;
+        for ((tag, count), result) in chunk.iter().zip(r) {
+            ret.push(MappingExportItem {
+                tag_type: tag.tag_type.clone(),
+                code: tag.code.clone(),
+                name: tag.name.clone(),
+                other_name: tag.other_name.clone(),
+                count: *count,
+                mappings: result.mappings.into_iter().map(|m| MappingExportTarget { meta_type: m.meta_type, id: m.meta_tag.id, name: m.meta_tag.name }).collect()
+            });
+        }
+    }
+    Result::Ok(ret)
+}
+
+fn write_export_file(f: &PathBuf, items: &Vec<MappingExportItem>) -> Result<(), Box<dyn Error>> {
+    let extension_str = f.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    let extension = extension_str.as_str();
+    let text = match extension {
+        "json" => serde_json::to_string_pretty(items)?,
+        "yaml" => serde_yaml::to_string(items)?,
+        _ => return Result::Err(Box::new(ApplicationError::new(&format!("Unsupported file type {}.", extension))))
+    };
+    std::fs::write(f, text)?;
+    Result::Ok(())
+}
+
+fn parse_target(target: &str) -> Result<SourceMappingTargetItem, Box<dyn Error>> {
+    let err = || ApplicationError::new(&format!("Mapping target '{}' is not in form of tag:<id>, topic:<id> or author:<id>.", target));
+    let (meta_type, id) = target.split_once(':').ok_or_else(err)?;
+    let meta_type = match meta_type.to_lowercase().as_str() {
+        "tag" => "TAG",
+        "topic" => "TOPIC",
+        "author" => "AUTHOR",
+        _ => return Result::Err(Box::new(err()))
+    };
+    let meta_id: i32 = id.parse().map_err(|_| err())?;
+    Result::Ok(SourceMappingTargetItem { meta_type: meta_type.to_string(), meta_id })
+}
+
+fn format_target(item: &SourceMappingTargetItemDetail) -> String {
+    format!("{} {} {}", item.meta_type, item.meta_tag.id, item.meta_tag.name)
+}
+
+#[derive(Serialize)]
+struct MappingExportItem {
+    #[serde(rename = "type")]
+    tag_type: String,
+    code: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(rename = "otherName", skip_serializing_if = "Option::is_none")]
+    other_name: Option<String>,
+    count: u32,
+    mappings: Vec<MappingExportTarget>
+}
+
+#[derive(Serialize)]
+struct MappingExportTarget {
+    #[serde(rename = "metaType")]
+    meta_type: String,
+    id: i32,
+    name: String
+}
@@ -1,11 +1,15 @@
 pub mod app;
 pub mod apply;
+pub mod dump;
 pub mod channel;
 pub mod server;
 pub mod import;
 pub mod source_data;
+pub mod mapping;
 pub mod tool;
+pub mod config;
 
+use std::io::{stdin, stdout, Write};
 use crate::module::channel::ChannelManager;
 use crate::module::config::LocalConfig;
 // use crate::module::local_data::LocalDataManager;
@@ -16,4 +20,15 @@ pub struct Context<'t> {
     // pub local_data_manager: &'t LocalDataManager,
     pub channel_manager: &'t ChannelManager<'t>,
     pub server_manager: &'t mut ServerManager
-}
\ No newline at end of file
+}
+
+/// 在终端中请求用户确认。只有输入y或yes时视为确认。
+pub fn confirm(message: &str) -> bool {
+    print!("{} [y/N] ", message);
+    stdout().flush().unwrap();
+    let mut input = String::new();
+    if stdin().read_line(&mut input).is_err() {
+        return false
+    }
+    matches!(input.trim().to_lowercase().as_str(), "y" | "yes")
+}
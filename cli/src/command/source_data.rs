@@ -1,5 +1,8 @@
-use crate::{module::{api::source_data::SourceDataModule, download::DownloadModule, connect::ConnectModule}, utils::error::ApiResultError};
-use super::Context;
+use std::{cell::Cell, collections::HashMap, error::Error, io::Write, path::PathBuf};
+use chrono::NaiveDate;
+use futures::future::join_all;
+use crate::{module::{api::{bulk::{BulkModule, SourceDataBulkForm, SourceTagForm, SourceBookForm, AdditionalInfoForm as BulkAdditionalInfoForm}, source_data::{AdditionalInfoForm, SourceDataDetailRes, SourceDataModule, SourceDataRes, SourceDataUpdateForm, SourceEditStatus}}, download::{DownloadModule, DownloadResult, CacheMode, attempts::DownloadAttempts}, connect::{ConnectModule, ConnectIdentity}, connect_state::{ConnectState, ConnectKey}, config::MergePolicy, import::SourceDataPath, server::ServerManager}, utils::error::ApiResultError};
+use super::{Context, confirm, apply::{ApplyFile, write_to_file}};
 
 
 pub async fn query(context: &mut Context<'_>, hql: &str, offset: u32, limit: u32) {
@@ -9,7 +12,7 @@ pub async fn query(context: &mut Context<'_>, hql: &str, offset: u32, limit: u32
     }
 
     let mut source_data_module = SourceDataModule::new(context.server_manager);
-    let r = match source_data_module.query(Option::Some(hql), Option::None, Option::None, Option::Some(offset), Option::Some(limit)).await {
+    let r = match source_data_module.query(Option::Some(hql), Option::None, Option::None, Option::None, Option::Some(offset), Option::Some(limit)).await {
         Err(e) => {
             eprintln!("Error occrred in requesting. {}", e.to_string());
             return
@@ -32,12 +35,320 @@ pub async fn query(context: &mut Context<'_>, hql: &str, offset: u32, limit: u32
     println!("Total {} result(s), current {} to {}.", r.total, offset + 1, offset + r.result.len() as u32);
 }
 
-pub async fn download(context: &mut Context<'_>) {
-    let sites: Vec<&str> = context.config.download.available_sites.iter().map(|f| f.site.as_str()).collect();
-    if sites.len() <= 0 {
+pub async fn show(context: &mut Context<'_>, site: &str, source_id: &str, json: bool) {
+    if let Err(e) = context.server_manager.waiting_for_start().await {
+        eprintln!("Cannot establish connection to server. {}", e);
+        return
+    }
+
+    let mut source_data_module = SourceDataModule::new(context.server_manager);
+    let detail = match source_data_module.get(site, source_id).await {
+        Err(e) => {
+            eprintln!("Error occrred in requesting. {}", e);
+            return
+        },
+        Ok(r) => r
+    };
+    let related_images = match source_data_module.get_related_images(site, source_id).await {
+        Err(e) => {
+            eprintln!("Error occrred in requesting. {}", e);
+            return
+        },
+        Ok(r) => r
+    };
+
+    if json {
+        let mut value = serde_json::to_value(&detail).unwrap();
+        value["relatedImages"] = serde_json::to_value(&related_images).unwrap();
+        println!("{}", serde_json::to_string_pretty(&value).unwrap());
+        return
+    }
+
+    println!("\x1b[1;33m{} ({}) {}\x1b[0m [{}]{}", detail.site_name, detail.site, detail.source_id, detail.status, if detail.empty { " (empty)" } else { "" });
+    if !detail.title.is_empty() { println!("Title:        {}", detail.title) }
+    if !detail.description.is_empty() { println!("Description:  {}", detail.description.replace('\n', "\n              ")) }
+    if let Some(publish_time) = &detail.publish_time { println!("Publish time: {}", publish_time) }
+    println!("Create time:  {}", detail.create_time);
+    println!("Update time:  {}", detail.update_time);
+    if !detail.tags.is_empty() {
+        println!("Tags ({}):", detail.tags.len());
+        for tag in &detail.tags {
+            let mut names = Vec::new();
+            if let Some(name) = &tag.name { names.push(name.as_str()) }
+            if let Some(other_name) = &tag.other_name { names.push(other_name.as_str()) }
+            println!("  - [{}] {}{}", tag.tag_type, tag.code, if names.is_empty() { String::new() } else { format!(" | {}", names.join(" / ")) });
+        }
+    }
+    if !detail.books.is_empty() {
+        println!("Books ({}):", detail.books.len());
+        for book in &detail.books {
+            println!("  - {} | {}{}", book.code, book.title, book.other_title.as_ref().map(|t| format!(" / {}", t)).unwrap_or_default());
+        }
+    }
+    if !detail.relations.is_empty() { println!("Relations ({}): {}", detail.relations.len(), detail.relations.join(", ")) }
+    if !detail.links.is_empty() {
+        println!("Links ({}):", detail.links.len());
+        for link in &detail.links {
+            println!("  - {}", link);
+        }
+    }
+    if !detail.additional_info.is_empty() {
+        println!("Additional info ({}):", detail.additional_info.len());
+        for info in &detail.additional_info {
+            println!("  - {} ({}): {}", info.label, info.field, info.value);
+        }
+    }
+    if !related_images.is_empty() {
+        println!("Related images ({}): {}", related_images.len(), related_images.iter().map(|i| i.id.to_string()).collect::<Vec<_>>().join(", "));
+    }
+}
+
+pub async fn export(context: &mut Context<'_>, hql: &str, output: &PathBuf) {
+    if let Err(e) = context.server_manager.waiting_for_start().await {
+        eprintln!("Cannot establish connection to server. {}", e);
+        return
+    }
+
+    let mut source_data_module = SourceDataModule::new(context.server_manager);
+    let items = match query_all(&mut source_data_module, Option::Some(hql), Option::None).await {
+        Err(e) => {
+            eprintln!("Error occrred in requesting. {}", e);
+            return
+        },
+        Ok(r) => r
+    };
+    let mut source_data = Vec::with_capacity(items.len());
+    for (i, item) in items.iter().enumerate() {
+        let detail = match source_data_module.get(&item.site, &item.source_id).await {
+            Err(e) => {
+                eprintln!("Error occrred in requesting {}-{}. {}", item.site, item.source_id, e);
+                return
+            },
+            Ok(r) => r
+        };
+        source_data.push(detail_to_bulk_form(detail));
+        print!("\rExporting {}/{}...", i + 1, items.len());
+        std::io::stdout().flush().unwrap();
+    }
+    if !items.is_empty() {
+        println!();
+    }
+
+    let content = ApplyFile { source_data: Option::Some(source_data), tags: Option::None, topics: Option::None, authors: Option::None, setting: Option::None };
+    if let Err(e) = write_to_file(output, &content) {
+        eprintln!("Cannot write to file {}. {}", output.to_str().unwrap(), e);
+        return
+    }
+    println!("Total {} source data exported to {}.", items.len(), output.to_str().unwrap());
+}
+
+pub async fn delete(context: &mut Context<'_>, site: Option<&str>, ids: &[String], hql: Option<&str>, yes: bool) {
+    if let Err(e) = context.server_manager.waiting_for_start().await {
+        eprintln!("Cannot establish connection to server. {}", e);
+        return
+    }
+
+    let mut source_data_module = SourceDataModule::new(context.server_manager);
+    let targets: Vec<(String, String)> = if let Some(hql) = hql {
+        match query_all(&mut source_data_module, Option::Some(hql), Option::None).await {
+            Err(e) => {
+                eprintln!("Error occrred in requesting. {}", e);
+                return
+            },
+            Ok(r) => r.into_iter().map(|i| (i.site, i.source_id)).collect()
+        }
+    }else{
+        let site = site.unwrap();
+        ids.iter().map(|id| (site.to_string(), id.clone())).collect()
+    };
+    if targets.is_empty() {
+        println!("No source data matched.");
+        return
+    }
+    if !yes && !confirm(&format!("Delete {} source data?", targets.len())) {
+        return
+    }
+
+    let mut success = 0;
+    let mut failed = 0;
+    for (site, source_id) in &targets {
+        match source_data_module.delete(site, source_id).await {
+            Err(e) => {
+                eprintln!("Error {}-{}: {}", site, source_id, e);
+                failed += 1;
+            },
+            Ok(_) => success += 1
+        }
+    }
+    println!("Delete source-data: {} succeed, {} failed.", success, failed);
+}
+
+pub async fn set_status(context: &mut Context<'_>, status: &SourceEditStatus, hql: &str, yes: bool) {
+    const BATCH_SIZE: usize = 1000;
+    if let Err(e) = context.server_manager.waiting_for_start().await {
+        eprintln!("Cannot establish connection to server. {}", e);
+        return
+    }
+
+    let mut source_data_module = SourceDataModule::new(context.server_manager);
+    let items = match query_all(&mut source_data_module, Option::Some(hql), Option::None).await {
+        Err(e) => {
+            eprintln!("Error occrred in requesting. {}", e);
+            return
+        },
+        Ok(r) => r
+    };
+    let status = status.to_json_code();
+    let items: Vec<SourceDataRes> = items.into_iter().filter(|i| i.status != status).collect();
+    if items.is_empty() {
+        println!("No source data need to be changed.");
+        return
+    }
+    if !yes && !confirm(&format!("Set status of {} source data to {}?", items.len(), status)) {
+        return
+    }
+
+    let mut bulk_module = BulkModule::new(context.server_manager);
+    let (mut success, mut failed) = (0, 0);
+    for chunk in items.chunks(BATCH_SIZE) {
+        let forms: Vec<SourceDataBulkForm> = chunk.iter().map(|i| SourceDataBulkForm { source_site: i.site.clone(), source_id: i.source_id.clone(), status: Option::Some(status.to_string()), title: Option::None, description: Option::None, tags: Option::None, books: Option::None, relations: Option::None, links: Option::None, additional_info: Option::None, publish_time: Option::None }).collect();
+        match bulk_module.source_data_bulk_update(&forms).await {
+            Err(e) => {
+                eprintln!("Bulk source-data failed. {}", e);
+                failed += forms.len() as i32;
+            },
+            Ok(result) => {
+                success += result.success;
+                failed += result.failed;
+                for e in result.errors {
+                    eprintln!("Error {}-{}: [{}]{}", e.target.source_site, e.target.source_id, e.error.code, e.error.message);
+                }
+            }
+        }
+    }
+    println!("Set status of source-data: {} succeed, {} failed.", success, failed);
+}
+
+pub async fn analyse(context: &mut Context<'_>, paths: &[PathBuf]) {
+    const BATCH_SIZE: usize = 500;
+    let mut filenames: Vec<String> = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let dir = match std::fs::read_dir(path) {
+                Ok(ok) => ok,
+                Err(e) => {
+                    eprintln!("Cannot read dir {}. {}", path.to_str().unwrap(), e);
+                    return
+                }
+            };
+            let mut names: Vec<String> = dir
+                .filter(|f| f.as_ref().is_ok_and(|f| f.file_type().is_ok_and(|f| f.is_file() || f.is_symlink())))
+                .map(|f| f.unwrap().file_name().to_str().unwrap().to_string())
+                .collect();
+            names.sort();
+            filenames.extend(names);
+        }else if let Some(name) = path.file_name() {
+            //不存在的路径也视作文件名，以便直接测试文件名而不需要真实文件
+            filenames.push(name.to_str().unwrap().to_string());
+        }
+    }
+    if filenames.is_empty() {
+        println!("No file to analyse.");
+        return
+    }
+
+    if let Err(e) = context.server_manager.waiting_for_start().await {
+        eprintln!("Cannot establish connection to server. {}", e);
+        return
+    }
+
+    let mut source_data_module = SourceDataModule::new(context.server_manager);
+    let (mut matched, mut unmatched, mut error) = (0, 0, 0);
+    for chunk in filenames.chunks(BATCH_SIZE) {
+        let chunk: Vec<&str> = chunk.iter().map(|f| f.as_str()).collect();
+        let result = match source_data_module.analyse_source_name(&chunk).await {
+            Err(e) => {
+                eprintln!("Error occrred in analyse source name request. {}", e);
+                return
+            },
+            Ok(r) => r
+        };
+        for r in result {
+            if let Some(e) = r.error {
+                error += 1;
+                println!("{:50}| \x1b[1;31m{}\x1b[0m", r.filename, e);
+            }else if let Some(source) = r.source {
+                matched += 1;
+                let mut s = format!("{} {}", source.source_site, source.source_id);
+                if let Some(part) = source.source_part { s.push_str(&format!(" part {}", part)) }
+                if let Some(part_name) = &source.source_part_name { s.push_str(&format!(" part name {}", part_name)) }
+                let image = if let Some(image_id) = r.image_id { format!("image \x1b[1;32m{}\x1b[0m", image_id) }else{ "no image".to_string() };
+                println!("{:50}| {} | {}", r.filename, s, image);
+            }else{
+                unmatched += 1;
+                println!("{:50}| \x1b[1;33mNo rule matched.\x1b[0m", r.filename);
+            }
+        }
+    }
+    println!("---");
+    println!("Total {} file(s): {} matched, {} unmatched, {} error.", filenames.len(), matched, unmatched, error);
+}
+
+/// 分页查询全部结果。
+pub async fn query_all(source_data_module: &mut SourceDataModule<'_>, hql: Option<&str>, site: Option<&str>) -> Result<Vec<SourceDataRes>, Box<dyn Error>> {
+    const PAGE_SIZE: u32 = 1000;
+    let mut items: Vec<SourceDataRes> = Vec::new();
+    loop {
+        let r = source_data_module.query(hql, Option::None, site.map(|s| vec![s]), Option::None, Option::Some(items.len() as u32), Option::Some(PAGE_SIZE)).await?;
+        let page_size = r.result.len() as u32;
+        items.extend(r.result);
+        if page_size < PAGE_SIZE || items.len() as i32 >= r.total { break }
+    }
+    Result::Ok(items)
+}
+
+pub fn detail_to_bulk_form(detail: SourceDataDetailRes) -> SourceDataBulkForm {
+    SourceDataBulkForm {
+        source_site: detail.site,
+        source_id: detail.source_id,
+        status: Option::Some(detail.status),
+        title: Option::Some(detail.title),
+        description: Option::Some(detail.description),
+        tags: Option::Some(detail.tags.into_iter().map(|t| SourceTagForm { code: t.code, name: t.name, other_name: t.other_name, tag_type: Option::Some(t.tag_type) }).collect()),
+        books: Option::Some(detail.books.into_iter().map(|b| SourceBookForm { code: b.code, title: Option::Some(b.title), other_title: b.other_title }).collect()),
+        relations: Option::Some(detail.relations),
+        links: Option::Some(detail.links),
+        additional_info: Option::Some(detail.additional_info.into_iter().map(|a| BulkAdditionalInfoForm { field: a.field, value: a.value }).collect()),
+        publish_time: detail.publish_time
+    }
+}
+
+pub struct DownloadOptions {
+    pub site: Vec<String>,
+    pub status: Vec<SourceEditStatus>,
+    pub hql: Option<String>,
+    pub limit: Option<u32>,
+    pub since: Option<NaiveDate>,
+    pub max_attempts: u32,
+    pub cache_mode: CacheMode
+}
+
+pub async fn download(context: &mut Context<'_>, options: &DownloadOptions) {
+    let DownloadOptions { site, status, hql, limit, since, max_attempts, cache_mode } = options;
+    let (hql, limit, since, max_attempts, cache_mode) = (hql.as_deref(), *limit, *since, *max_attempts, *cache_mode);
+    let sites: Vec<&str> = if site.is_empty() {
+        context.config.download.available_sites.iter().map(|f| f.site.as_str()).collect()
+    }else if let Some(s) = site.iter().find(|s| !context.config.download.available_sites.iter().any(|f| &f.site == *s)) {
+        eprintln!("Site {} not configured in available sites.", s);
+        return
+    }else{
+        site.iter().map(|f| f.as_str()).collect()
+    };
+    if sites.is_empty() {
         eprintln!("Available sites not configured.");
         return
     }
+    let status: Vec<&str> = if status.is_empty() { vec!["NOT_EDITED", "ERROR"] }else{ status.iter().map(|f| f.to_json_code()).collect() };
 
     if let Err(e) = context.server_manager.maintaining_for_start().await {
         eprintln!("Cannot establish connection to server. {}", e);
@@ -45,180 +356,468 @@ pub async fn download(context: &mut Context<'_>) {
     }
 
     let mut source_data_module = SourceDataModule::new(context.server_manager);
-    let r = match source_data_module.query(Option::None, Option::Some(vec!["NOT_EDITED", "ERROR"]), Option::Some(sites), Option::None, Option::Some(1000)).await {
+    let (total, items) = match query_for_download(&mut source_data_module, hql, &status, &sites, limit, since).await {
         Err(e) => {
-            eprintln!("Error occrred in requesting. {}", e.to_string());
+            eprintln!("Error occrred in requesting. {}", e);
             return
         },
         Ok(r) => r
     };
-    if r.result.len() <= 0 {
-        println!("Total {} result(s) found.", r.total);
+
+    let download_module = DownloadModule::new(&context.config, cache_mode);
+
+    //失败次数达到上限的项不再重试；离线模式下，没有缓存的项也无法处理
+    let attempts = DownloadAttempts::new(&context.config, context.channel_manager.current_channel());
+    let (items, exceeded): (Vec<SourceDataRes>, Vec<SourceDataRes>) = items.into_iter().partition(|f| attempts.get(&f.site, &f.source_id) < max_attempts);
+    let (items, uncached): (Vec<SourceDataRes>, Vec<SourceDataRes>) = items.into_iter().partition(|f| cache_mode != CacheMode::Offline || download_module.is_cached(&f.site, &f.source_id));
+    let mut skipped = Vec::new();
+    if !exceeded.is_empty() { skipped.push(format!("skipped {} result(s) that reached max attempts", exceeded.len())) }
+    if !uncached.is_empty() { skipped.push(format!("skipped {} result(s) not cached", uncached.len())) }
+    let skipped = if skipped.is_empty() { "".to_string() }else{ format!(" {}.", skipped.join(", ")) };
+
+    if items.is_empty() {
+        println!("Total {} result(s) found.{}", total, skipped);
         return
     }
 
-    println!("Total {} result(s) found. Current processing {} result(s).", r.total, r.result.len());
+    println!("Total {} result(s) found. Current processing {} result(s).{}", total, items.len(), skipped);
     println!("---");
     
-    let download_module = DownloadModule::new(&context.config);
+    let error_fields: HashMap<&str, &str> = context.config.download.available_sites.iter().filter_map(|f| f.error_field.as_ref().map(|e| (f.site.as_str(), e.as_str()))).collect();
+
+    //按site分组，不同site之间并发下载，同一site内顺序下载，速率由每个site各自的限流器控制
+    let mut groups: Vec<(&str, Vec<&SourceDataRes>)> = Vec::new();
+    for item in &items {
+        if let Some((_, group)) = groups.iter_mut().find(|(site, _)| *site == item.site) {
+            group.push(item);
+        }else{
+            groups.push((&item.site, vec![item]));
+        }
+    }
 
-    let result_count = r.result.len();
-    let result_count_str_len = result_count.to_string().len();
-    let mut index = 1;
+    let runner = DownloadRunner { download_module: &download_module, attempts: &attempts, error_fields: &error_fields, merge: &context.config.merge, index: Cell::new(0), count: items.len() };
+    let server_manager: &ServerManager = context.server_manager;
+    let results = join_all(groups.iter().map(|(_, group)| download_site_items(SourceDataModule::new(server_manager), &runner, group))).await;
+    let success: i32 = results.iter().map(|(s, _)| s).sum();
+    let failed: i32 = results.iter().map(|(_, f)| f).sum();
+    
+    println!("---");
+    if failed > 0 {
+        println!("Processing completed. Success {} item(s), failed \x1b[1;31m{}\x1b[0m item(s).", success, failed);
+    }else{
+        println!("Processing completed. Success {} item(s), failed 0 item(s).", success);
+    }
+}
+
+struct DownloadRunner<'a> {
+    download_module: &'a DownloadModule,
+    attempts: &'a DownloadAttempts,
+    error_fields: &'a HashMap<&'a str, &'a str>,
+    merge: &'a MergePolicy,
+    index: Cell<usize>,
+    count: usize
+}
+
+async fn download_site_items(mut source_data_module: SourceDataModule<'_>, runner: &DownloadRunner<'_>, items: &[&SourceDataRes]) -> (i32, i32) {
     let mut success = 0;
     let mut failed = 0;
-    for item in &r.result {
+    for item in items {
         //tips: 暂时没有需要additional info的实现。如果有实现，需要根据config的配置，决定哪些需要附加信息，然后对此site查询详情
-        let dn = download_module.download(&item.site, &item.source_id, Option::None).await;
+        let dn = runner.download_module.download(&item.site, &item.source_id, Option::None).await;
+        let error_field = runner.error_fields.get(item.site.as_str()).copied();
 
-        let date = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
-        print!("{} | {:>rc_len$}/{} \x1b[1;33m| {:16} | {:>12} |\x1b[0m", date, index, result_count, item.site, item.source_id, rc_len = result_count_str_len);
-
-        match dn {
+        let message = match dn {
             Ok((result, info)) => {
-                let form = result.to_update_form();
-
-                match source_data_module.update(&item.site, &item.source_id, &form).await {
+                match update_downloaded(&mut source_data_module, item, error_field, runner.merge, result).await {
                     Ok(()) => {
-                        println!("\x1b[1;32m Success (in {:.2}s, retry {} time(s))\x1b[0m", (info.time_cost as f64) / 1000.0, info.retry_count);
+                        runner.attempts.clear(&item.site, &item.source_id);
                         success += 1;
+                        format!("\x1b[1;32m Success (in {:.2}s, retry {} time(s))\x1b[0m", (info.time_cost as f64) / 1000.0, info.retry_count)
                     },
                     Err(e) => {
-                        println!("\x1b[1;31m Success (in {:.2}s, retry {} time(s)), But update failed: {}\x1b[0m", (info.time_cost as f64) / 1000.0, info.retry_count, e);
                         failed += 1;
+                        format!("\x1b[1;31m Success (in {:.2}s, retry {} time(s)), But update failed: {}\x1b[0m", (info.time_cost as f64) / 1000.0, info.retry_count, e)
                     }
                 }
-
-                if index < result_count {
-                    download_module.wait(info.time_cost).await;
-                }
             },
             Err(e) => {
-                println!("\x1b[1;31m Failed: {}\x1b[0m", e);
                 failed += 1;
-
-                if index < result_count {
-                    download_module.wait(0).await;
+                let attempts = runner.attempts.increase(&item.site, &item.source_id);
+                match mark_as_error(&mut source_data_module, &item.site, &item.source_id, error_field, &e.to_string()).await {
+                    Ok(()) => format!("\x1b[1;31m Failed (attempt {} time(s)): {}\x1b[0m", attempts, e),
+                    Err(ue) => format!("\x1b[1;31m Failed (attempt {} time(s)): {}, And mark as ERROR failed: {}\x1b[0m", attempts, e, ue)
                 }
             }
+        };
+
+        runner.index.set(runner.index.get() + 1);
+        let date = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
+        println!("{} | {:>rc_len$}/{} \x1b[1;33m| {:16} | {:>12} |\x1b[0m{}", date, runner.index.get(), runner.count, item.site, item.source_id, message, rc_len = runner.count.to_string().len());
+    }
+    (success, failed)
+}
+
+async fn update_downloaded(source_data_module: &mut SourceDataModule<'_>, item: &SourceDataRes, error_field: Option<&str>, merge: &MergePolicy, result: DownloadResult) -> Result<(), Box<dyn Error>> {
+    let clear_error = error_field.is_some() && item.status == "ERROR";
+    //合并策略需要对照现有的值计算；曾经失败过的项，也需要现有的值以清除之前记录的失败原因
+    let detail = if !merge.is_replace() || (clear_error && result.additional_info.is_none()) {
+        Option::Some(source_data_module.get(&item.site, &item.source_id).await?)
+    }else{
+        Option::None
+    };
+    let mut form = if let Some(ref detail) = detail { result.merge(detail, merge).to_update_form() }else{ result.to_update_form() };
+    if let (Some(error_field), true) = (error_field, clear_error) {
+        if let Some(ref mut info) = form.additional_info {
+            info.retain(|f| f.field != error_field);
+        }else if let Some(ref detail) = detail {
+            form.additional_info = Option::Some(replace_error_reason(detail, error_field, Option::None));
         }
-        index += 1;
     }
-    
-    println!("---");
-    if failed > 0 {
-        println!("Processing completed. Success {} item(s), failed \x1b[1;31m{}\x1b[0m item(s).", success, failed);
+    source_data_module.update(&item.site, &item.source_id, &form).await
+}
+
+/// 将下载失败的项标记为ERROR。如果此site配置了error_field，还会将失败原因写入此additional info字段。
+async fn mark_as_error(source_data_module: &mut SourceDataModule<'_>, site: &str, source_id: &str, error_field: Option<&str>, reason: &str) -> Result<(), Box<dyn Error>> {
+    let additional_info = if let Some(error_field) = error_field {
+        //additional info的更新是整体覆盖的，因此需要先取得现有的值
+        let detail = source_data_module.get(site, source_id).await?;
+        Option::Some(replace_error_reason(&detail, error_field, Option::Some(reason)))
     }else{
-        println!("Processing completed. Success {} item(s), failed 0 item(s).", success);
+        Option::None
+    };
+    let form = SourceDataUpdateForm { status: Option::Some("ERROR".to_string()), additional_info, title: Option::None, description: Option::None, tags: Option::None, books: Option::None, relations: Option::None };
+    source_data_module.update(site, source_id, &form).await
+}
+
+fn replace_error_reason(detail: &SourceDataDetailRes, error_field: &str, reason: Option<&str>) -> Vec<AdditionalInfoForm> {
+    let mut ret: Vec<AdditionalInfoForm> = detail.additional_info.iter().filter(|f| f.field != error_field).map(|f| AdditionalInfoForm { field: f.field.clone(), value: f.value.clone() }).collect();
+    if let Some(reason) = reason {
+        ret.push(AdditionalInfoForm { field: error_field.to_string(), value: reason.to_string() });
+    }
+    ret
+}
+
+/// 分页查询所有待下载的项，直到查询结果耗尽或达到limit。
+/// 由于下载成功的项会改变status，进而从查询结果中移除，因此必须在开始下载前完成全部查询，而不能边下载边翻页。
+async fn query_for_download(source_data_module: &mut SourceDataModule<'_>, hql: Option<&str>, status: &[&str], sites: &[&str], limit: Option<u32>, since: Option<NaiveDate>) -> Result<(i32, Vec<SourceDataRes>), Box<dyn Error>> {
+    const PAGE_SIZE: u32 = 1000;
+    let order = if since.is_some() { Option::Some("-createTime") }else{ Option::None };
+    let mut total = 0;
+    let mut items: Vec<SourceDataRes> = Vec::new();
+    let mut offset = 0;
+    loop {
+        let page_limit = if let Some(limit) = limit { PAGE_SIZE.min(limit - items.len() as u32) }else{ PAGE_SIZE };
+        if page_limit == 0 { break }
+        let r = source_data_module.query(hql, Option::Some(status.to_vec()), Option::Some(sites.to_vec()), order, Option::Some(offset), Option::Some(page_limit)).await?;
+        total = r.total;
+        let page_size = r.result.len() as u32;
+        if let Some(since) = since {
+            //按createTime倒序查询，因此遇到第一个早于since的项时，后续的项也都早于since
+            let mut reached = false;
+            for item in r.result {
+                if chrono::DateTime::parse_from_rfc3339(&item.create_time)?.with_timezone(&chrono::Local).date_naive() < since {
+                    reached = true;
+                    break
+                }
+                items.push(item);
+            }
+            if reached { break }
+        }else{
+            items.extend(r.result);
+        }
+        offset += page_size;
+        if page_size < page_limit || offset as i32 >= total { break }
     }
+    Result::Ok((total, items))
+}
+
+pub struct ConnectOptions {
+    pub split: Vec<String>,
+    pub limit: Option<u32>,
+    pub update: bool,
+    pub verbose: bool,
+    pub vars: Vec<(String, String)>,
+    pub resume: bool,
+    pub since: bool,
+    pub dry_run: bool
 }
 
-pub async fn connect(context: &mut Context<'_>, split: &Vec<String>, limit: Option<u32>, update: bool, verbose: bool) {
-    if split.len() <= 0 { 
+pub async fn connect(context: &mut Context<'_>, options: &ConnectOptions) {
+    let ConnectOptions { split, limit, update, verbose, vars, resume, since, dry_run } = options;
+    let (limit, update, verbose, resume, since, dry_run) = (*limit, *update, *verbose, *resume, *since, *dry_run);
+    if split.is_empty() {
         eprintln!("Must specify at least one split.");
         return
     }
-    if let Err(e) = context.server_manager.maintaining_for_start().await {
-        eprintln!("Cannot establish connection to server. {}", e);
-        return
+    //dry run不连接服务器，也不记录进度
+    if !dry_run {
+        if let Err(e) = context.server_manager.maintaining_for_start().await {
+            eprintln!("Cannot establish connection to server. {}", e);
+            return
+        }
     }
-    let mut source_data_module = SourceDataModule::new(&context.server_manager);
+    let mut source_data_module = SourceDataModule::new(context.server_manager);
+    let mut bulk_module = BulkModule::new(context.server_manager);
     let connect_module = ConnectModule::new(&context.config);
     
     let mut conn = match connect_module.connect() {
         Ok(c) => c,
         Err(e) => {
-            eprintln!("Error occrred in connecting. {}", e.to_string());
+            eprintln!("Error occrred in connecting. {}", e);
             return
         }
     };
-    let mut stat = match conn.statement(split, limit) {
-        Ok(s) => s,
-        Err(e) => {
-            eprintln!("Error occrred in statement preparing. {}", e.to_string());
+    //文件的url中没有$split时，每个split都会读取整个文件
+    if split.len() > 1 && !conn.splittable() {
+        eprintln!("Only one split can be given because connect.url does not contain $split.");
+        return
+    }
+
+    let mut state = ConnectState::new(&context.config, context.channel_manager.current_channel());
+    let mut counter = ConnectCounter { index: 0, created: 0, updated: 0, skipped: 0, failed: 0 };
+    //每个split分开查询，以便分别记录进度
+    for s in split {
+        let remaining = match limit {
+            Some(l) if counter.index as u32 >= l => break,
+            Some(l) => Option::Some(l - counter.index as u32),
+            None => Option::None
+        };
+        let cursor = if resume {
+            state.get(s).and_then(|st| st.cursor.clone())
+        }else if since {
+            state.get(s).and_then(|st| st.since.clone())
+        }else{
+            Option::None
+        };
+        let mut stat = match conn.statement(std::slice::from_ref(s), remaining, vars, cursor.as_ref()) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("Error occrred in statement preparing. {}", e);
+                return
+            }
+        };
+        if (resume || since) && !stat.has_key() {
+            eprintln!("Cannot find key column. Please specifiy a column named key to use --resume or --since.");
             return
         }
-    };
 
-    let mut index = 0;
-    let mut success = 0;
-    while let Some((identity, result)) = stat.next() {
+        let failed_before = counter.failed;
+        let mut stalled = false;
+        let mut chunk: Vec<ConnectRow> = Vec::with_capacity(CONNECT_CHUNK_SIZE);
+        loop {
+            let row = stat.next().map(|(identity, result)| ConnectRow { index: counter.index + chunk.len() + 1, identity, result, key: stat.key() });
+            let end = row.is_none();
+            if let Some(row) = row { chunk.push(row) }
+            if chunk.len() >= CONNECT_CHUNK_SIZE || (end && !chunk.is_empty()) {
+                counter.index += chunk.len();
+                if dry_run {
+                    connect_chunk_dry_run(&chunk, &mut counter);
+                    chunk.clear();
+                    if end { break }else{ continue }
+                }
+                let first_failed = connect_chunk(&mut source_data_module, &mut bulk_module, &chunk, update, &context.config.merge, verbose, &mut counter).await;
+                //只在一批记录写入完成后才推进进度，中断时未提交的记录会在--resume时重新处理。
+                //进度最多推进到第一条失败的记录之前，此后不再推进，使失败的记录能在--resume时重试
+                if !stalled {
+                    let done = match first_failed {
+                        Some(i) => { stalled = true; &chunk[..i] },
+                        None => &chunk[..]
+                    };
+                    if let Some(key) = done.last().and_then(|r| r.key.clone()) {
+                        state.set_cursor(s, key);
+                    }
+                }
+                chunk.clear();
+            }
+            if end { break }
+        }
+        if stat.has_key() && !dry_run {
+            if counter.failed == failed_before {
+                state.complete(s);
+            }else{
+                eprintln!("Some items of split '{}' failed. Progress of --since is not advanced.", s);
+            }
+        }
+    }
+
+    let ConnectCounter { index, created, updated, skipped, failed } = counter;
+    if dry_run {
+        //结果输出到stdout，统计输出到stderr，以便重定向结果
+        eprintln!("Dry run completed. Parsed {} item(s), failed {} item(s).", index - failed, failed);
+        return
+    }
+    if verbose { println!("---") }else if index > 0 { println!() }
+    if failed > 0 {
+        println!("Processing completed. Created {} item(s), updated {} item(s), skipped {} item(s), failed \x1b[1;31m{}\x1b[0m item(s).", created, updated, skipped, failed);
+    }else{
+        println!("Processing completed. Created {} item(s), updated {} item(s), skipped {} item(s), failed 0 item(s).", created, updated, skipped);
+    }
+}
+
+const CONNECT_CHUNK_SIZE: usize = 500;
+
+struct ConnectRow {
+    index: usize,
+    identity: Option<ConnectIdentity>,
+    result: Result<DownloadResult, Box<dyn Error>>,
+    key: Option<ConnectKey>
+}
+
+struct ConnectCounter {
+    index: usize,
+    created: usize,
+    updated: usize,
+    skipped: usize,
+    failed: usize
+}
+
+enum ConnectOutcome {
+    Created,
+    Updated,
+    NotEditedUpdated,
+    Skipped,
+    Duplicated
+}
+
+/// 将一批connect读取到的记录写入服务器，并输出每条记录的处理结果。返回第一条失败的记录在批次中的位置。
+async fn connect_chunk(source_data_module: &mut SourceDataModule<'_>, bulk_module: &mut BulkModule<'_>, rows: &[ConnectRow], update: bool, merge: &MergePolicy, verbose: bool, counter: &mut ConnectCounter) -> Option<usize> {
+    let outcomes = connect_chunk_outcomes(source_data_module, bulk_module, rows, update, merge).await;
+    let mut first_failed = Option::None;
+    for (i, (row, outcome)) in rows.iter().zip(outcomes).enumerate() {
+        match &outcome {
+            Ok(ConnectOutcome::Created) => counter.created += 1,
+            Ok(ConnectOutcome::Updated) | Ok(ConnectOutcome::NotEditedUpdated) => counter.updated += 1,
+            Ok(ConnectOutcome::Skipped) | Ok(ConnectOutcome::Duplicated) => counter.skipped += 1,
+            Err(_) => {
+                counter.failed += 1;
+                first_failed.get_or_insert(i);
+            }
+        }
+
         if verbose {
             let date = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
-            if let Some((site, id, _)) = &identity {
-                print!("{} | {:>4} \x1b[1;33m| {:16} | {:>12} |\x1b[0m", date, index + 1, site, id);
+            if let Some((site, id, _)) = &row.identity {
+                print!("{} | {:>4} \x1b[1;33m| {:16} | {:>12} |\x1b[0m", date, row.index, site, id);
             }else{
-                print!("{} | {:>4} \x1b[1;33m|\x1b[0m", date, index + 1);
+                print!("{} | {:>4} \x1b[1;33m|\x1b[0m", date, row.index);
+            }
+            let info = row.result.as_ref().map(|r| r.info()).unwrap_or_default();
+            match outcome {
+                Ok(ConnectOutcome::Created) => println!("\x1b[1;32m Created. {}\x1b[0m", info),
+                Ok(ConnectOutcome::Updated) => println!("\x1b[1;32m Updated. {}\x1b[0m", info),
+                Ok(ConnectOutcome::NotEditedUpdated) => println!("\x1b[1;32m NOT_EDITED exists, Updated. {}\x1b[0m", info),
+                Ok(ConnectOutcome::Skipped) => println!("\x1b[1;33m Already exists, skip it.\x1b[0m"),
+                Ok(ConnectOutcome::Duplicated) => println!("\x1b[1;33m Duplicated later in this batch, skip it.\x1b[0m"),
+                Err(e) => println!("\x1b[1;31m Failed. {}\x1b[0m", e)
             }
-            match &result {
-                Ok(result) => {
-                    let (site, id, _) = &identity.unwrap();
-                    let form = result.to_update_form();
-                    match source_data_module.create(site, &id, &form).await {
-                        Ok(_) => {
-                            println!("\x1b[1;32m Created. {}\x1b[0m", result.info());
-                            success += 1;
-                        },
-                        Err(e) => if let Some(e) = e.downcast_ref::<ApiResultError>() {
-                            if e.code == "ALREADY_EXISTS" {
-                                if update {
-                                    match source_data_module.update(site, &id, &form).await {
-                                        Ok(_) => {
-                                            println!("\x1b[1;32m Updated. {}\x1b[0m", result.info());
-                                            success += 1;
-                                        },
-                                        Err(e) => if let Some(e) = e.downcast_ref::<ApiResultError>() {
-                                            println!("\x1b[1;31m Failed. {}\x1b[0m", e)
-                                        }else{
-                                            println!("\x1b[1;31m Failed. Request error: {}\x1b[0m", e)
-                                        }
-                                    }
-                                }else{
-                                    match source_data_module.get(site, &id).await {
-                                        Ok(sd) => {
-                                            if sd.status == "NOT_EDITED" {
-                                                match source_data_module.update(site, &id, &form).await {
-                                                    Ok(_) => {
-                                                        println!("\x1b[1;32m NOT_EDITED exists, Updated. {}\x1b[0m", result.info());
-                                                        success += 1;
-                                                    },
-                                                    Err(e) => if let Some(e) = e.downcast_ref::<ApiResultError>() {
-                                                        println!("\x1b[1;31m Failed. {}\x1b[0m", e)
-                                                    }else{
-                                                        println!("\x1b[1;31m Failed. Request error: {}\x1b[0m", e)
-                                                    }
-                                                }
-                                            }else{
-                                                println!("\x1b[1;33m Already exists, skip it.\x1b[0m");
-                                                success += 1;
-                                            }
-                                        },
-                                        Err(e) => if let Some(e) = e.downcast_ref::<ApiResultError>() {
-                                            println!("\x1b[1;31m Failed. {}\x1b[0m", e)
-                                        }else{
-                                            println!("\x1b[1;31m Failed. Request error: {}\x1b[0m", e)
-                                        }
-                                    }
-                                }
-                            }else{
-                                println!("\x1b[1;31m Failed. {}\x1b[0m", e)
-                            }
-                        }else{
-                            println!("\x1b[1;31m Failed. Request error: {}\x1b[0m", e)
-                        }
+        }
+    }
+    if !verbose {
+        print!("\rProcessing {} item(s)...", counter.index);
+        std::io::stdout().flush().unwrap();
+    }
+    first_failed
+}
+
+/// 不写入服务器，将每条记录的解析结果以JSON Lines输出到stdout，解析失败的记录输出到stderr。
+fn connect_chunk_dry_run(rows: &[ConnectRow], counter: &mut ConnectCounter) {
+    for row in rows {
+        match (&row.identity, &row.result) {
+            (Some((site, id, part)), Ok(result)) => {
+                println!("{}", serde_json::json!({ "site": site, "id": id, "part": part, "result": result }));
+            },
+            (_, Err(e)) => {
+                counter.failed += 1;
+                eprintln!("{:>4} \x1b[1;31m| Record parse error: {}\x1b[0m", row.index, e);
+            },
+            (None, Ok(_)) => {
+                counter.failed += 1;
+                eprintln!("{:>4} \x1b[1;31m| Record has no identity.\x1b[0m", row.index);
+            }
+        }
+    }
+}
+
+/// 先通过collect-status一次性查询这批记录的现状，已存在的记录只在指定update或其状态仍为NOT_EDITED时更新；
+/// 随后将需要写入的记录通过bulk接口一次性提交。同一批中重复的来源只提交最后一条，与逐条处理时后者覆盖前者的结果一致。
+async fn connect_chunk_outcomes(source_data_module: &mut SourceDataModule<'_>, bulk_module: &mut BulkModule<'_>, rows: &[ConnectRow], update: bool, merge: &MergePolicy) -> Vec<Result<ConnectOutcome, String>> {
+    let mut outcomes: Vec<Option<Result<ConnectOutcome, String>>> = Vec::with_capacity(rows.len());
+    let mut lasts: HashMap<(&str, &str), usize> = HashMap::new();
+    for (i, row) in rows.iter().enumerate() {
+        let outcome = match (&row.identity, &row.result) {
+            (Some((site, id, _)), Ok(_)) => {
+                if let Some(prev) = lasts.insert((site, id), i) {
+                    outcomes[prev] = Option::Some(Result::Ok(ConnectOutcome::Duplicated));
+                }
+                Option::None
+            },
+            (_, Err(e)) => Option::Some(Result::Err(format!("Record parse error: {}", e))),
+            (None, Ok(_)) => Option::Some(Result::Err("Record has no identity.".to_string()))
+        };
+        outcomes.push(outcome);
+    }
+
+    let paths: Vec<SourceDataPath> = lasts.keys().map(|(site, id)| SourceDataPath { source_site: site.to_string(), source_id: id.to_string(), source_part: Option::None, source_part_name: Option::None }).collect();
+    let status: HashMap<(String, String), Option<String>> = if paths.is_empty() { HashMap::new() }else{
+        match source_data_module.collect_status(&paths).await {
+            Ok(r) => r.into_iter().map(|s| ((s.source.source_site, s.source.source_id), s.collect_status)).collect(),
+            Err(e) => {
+                let message = request_error_message(e.as_ref());
+                return outcomes.into_iter().map(|o| o.unwrap_or_else(|| Result::Err(message.clone()))).collect()
+            }
+        }
+    };
+
+    let mut forms = Vec::new();
+    for (&(site, id), &i) in lasts.iter() {
+        let outcome = match status.get(&(site.to_string(), id.to_string())).and_then(|s| s.as_deref()) {
+            None => ConnectOutcome::Created,
+            Some(_) if update => ConnectOutcome::Updated,
+            Some("NOT_EDITED") => ConnectOutcome::NotEditedUpdated,
+            Some(_) => ConnectOutcome::Skipped
+        };
+        if let (false, Ok(result)) = (matches!(outcome, ConnectOutcome::Skipped), &rows[i].result) {
+            if matches!(outcome, ConnectOutcome::Created) || merge.is_replace() {
+                forms.push(result.to_bulk_form(site, id));
+            }else{
+                //更新已存在的项时，按合并策略对照现有的值计算需要提交的结果
+                match source_data_module.get(site, id).await {
+                    Ok(detail) => forms.push(result.merge(&detail, merge).to_bulk_form(site, id)),
+                    Err(e) => {
+                        outcomes[i] = Option::Some(Result::Err(request_error_message(e.as_ref())));
+                        continue
                     }
-                },
-                Err(e) => println!("\x1b[1;31m Failed. Record parse error: {}\x1b[0m", e)
+                }
             }
         }
+        outcomes[i] = Option::Some(Result::Ok(outcome));
+    }
 
-        index += 1;
+    if !forms.is_empty() {
+        match bulk_module.source_data_bulk_update(&forms).await {
+            Ok(r) => for error in r.errors {
+                if let Some(&i) = lasts.get(&(error.target.source_site.as_str(), error.target.source_id.as_str())) {
+                    outcomes[i] = Option::Some(Result::Err(format!("[{}]{}", error.error.code, error.error.message)));
+                }
+            },
+            Err(e) => {
+                let message = request_error_message(e.as_ref());
+                for form in forms.iter() {
+                    if let Some(&i) = lasts.get(&(form.source_site.as_str(), form.source_id.as_str())) {
+                        outcomes[i] = Option::Some(Result::Err(message.clone()));
+                    }
+                }
+            }
+        }
     }
 
-    if verbose { println!("---") }
-    if index - success > 0 {
-        println!("Processing completed. Success {} item(s), failed \x1b[1;31m{}\x1b[0m item(s).", success, index - success);
+    outcomes.into_iter().map(|o| o.unwrap_or_else(|| Result::Err("Record not processed.".to_string()))).collect()
+}
+
+fn request_error_message(e: &(dyn Error + 'static)) -> String {
+    if let Some(e) = e.downcast_ref::<ApiResultError>() {
+        e.to_string()
     }else{
-        println!("Processing completed. Success {} item(s), failed 0 item(s).", success);
+        format!("Request error: {}", e)
     }
-}
\ No newline at end of file
+}
@@ -2,9 +2,9 @@ use std::path::PathBuf;
 use async_std::fs;
 use serde::Deserialize;
 
-use crate::module::api::{source_data::SourceDataModule, folder::{FolderModule, FolderCreateForm}};
+use crate::module::{api::{source_data::SourceDataModule, folder::{FolderModule, FolderCreateForm}}, source_analyse::SourceAnalyser};
 
-use super::Context;
+use super::{Context, apply::read_from_file};
 
 pub async fn import_folder(context: &mut Context<'_>, file: &Option<PathBuf>, tree: &Option<PathBuf>, dry_run: bool) {
     if let Err(e) = context.server_manager.waiting_for_start().await {
@@ -146,6 +146,48 @@ async fn process_one_direcory(source_data_module: &mut SourceDataModule<'_>, fol
     println!()
 }
 
+pub fn test_rules(rules: &PathBuf, filenames: &[String]) {
+    let file = match read_from_file(rules) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("Rules file read error. {}", e);
+            return
+        }
+    };
+    let rules = match file.setting.and_then(|s| s.import).and_then(|i| i.source_analyse_rules) {
+        Some(r) if !r.is_empty() => r,
+        _ => {
+            eprintln!("No setting.import.sourceAnalyseRules found in rules file.");
+            return
+        }
+    };
+
+    let analyser = SourceAnalyser::new(&rules);
+    let compile_errors = analyser.compile_errors();
+    for (i, rule, e) in &compile_errors {
+        println!("Rule #{} ({}): \x1b[1;31m{}\x1b[0m", i + 1, rule.site, e);
+    }
+    if !compile_errors.is_empty() {
+        println!("---");
+    }
+
+    for filename in filenames {
+        match analyser.analyse(filename) {
+            Err(e) => println!("{:50}| \x1b[1;31m{}\x1b[0m", filename, e),
+            Ok(None) => println!("{:50}| \x1b[1;33mNo rule matched.\x1b[0m", filename),
+            Ok(Some(r)) => {
+                let mut s = format!("{} {}", r.site, r.id);
+                if let Some(part) = r.part { s.push_str(&format!(" part {}", part)) }
+                if let Some(part_name) = &r.part_name { s.push_str(&format!(" part name {}", part_name)) }
+                println!("{:50}| \x1b[1;32m{}\x1b[0m (rule #{})", filename, s, r.rule_index + 1);
+                for extra in &r.extras {
+                    println!("{:50}|   {}: {}", "", extra.target, extra.value);
+                }
+            }
+        }
+    }
+}
+
 #[derive(Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 enum TreeJsonNode {
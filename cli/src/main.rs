@@ -5,11 +5,14 @@ mod utils;
 
 use clap::{Parser, CommandFactory};
 use clap_complete::generate;
-use cli::{Cli, Import, Channel, Server, SourceData, Tool};
-use command::apply::ApplyInputType;
+use cli::{Cli, ApplyCommand, Import, Channel, Server, SourceData, Mapping, Tool, Config};
+use command::apply::{ApplyInputType, ApplyOptions};
+use command::dump::DumpOptions;
+use command::source_data::{DownloadOptions, ConnectOptions};
 use module::local_data::LocalDataManager;
 use module::channel::ChannelManager;
 use module::server::ServerManager;
+use module::download::CacheMode;
 
 #[tokio::main]
 async fn main() {
@@ -37,7 +40,9 @@ async fn main() {
             Server::Kill => command::server::kill(&mut context).await,
             Server::Log{ date } => command::server::log(&mut context, date).await
         }
-        Cli::Apply(apply) => {
+        Cli::Apply(apply) => if let Some(ApplyCommand::Schema) = apply.command {
+            command::apply::schema()
+        }else{
             let mut input: Vec<ApplyInputType> = Vec::new();
             if let Some(f) = apply.directory {
                 for ele in f {
@@ -54,10 +59,13 @@ async fn main() {
             }
             if input.is_empty() {
                 eprintln!("Options --directory, --file and --input should have least one.")
+            }else if apply.check {
+                command::apply::check(&input)
             }else{
-                command::apply::apply(&mut context, &input, apply.verbose).await
+                command::apply::apply(&mut context, &input, &ApplyOptions { verbose: apply.verbose, dry_run: apply.dry_run, diff: apply.diff }).await
             }
         }
+        Cli::Dump(dump) => command::dump::dump(&mut context, &DumpOptions { tags: dump.tags, topics: dump.topics, authors: dump.authors, settings: dump.settings, source_data: dump.source_data, output: dump.output, format: dump.format }).await,
         Cli::Import(import) => match import {
             Import::Add { files, remove } => command::import::add(&mut context, &files, remove).await,
             Import::Batch { partition_time, create_time, order_time, analyse_source } => command::import::batch(&mut context, partition_time, create_time, order_time, analyse_source).await,
@@ -66,11 +74,31 @@ async fn main() {
         }
         Cli::SourceData(source_data) => match source_data {
             SourceData::Query { hql, limit, offset } => command::source_data::query(&mut context, hql.as_str(), offset, limit).await,
-            SourceData::Download => command::source_data::download(&mut context).await,
-            SourceData::Connect { split, limit, update, verbose } => command::source_data::connect(&mut context, &split, limit, update, verbose).await
+            SourceData::Show { site, id, json } => command::source_data::show(&mut context, &site, &id, json).await,
+            SourceData::Export { hql, output } => command::source_data::export(&mut context, &hql, &output).await,
+            SourceData::Delete { site, ids, query, yes } => command::source_data::delete(&mut context, site.as_deref(), &ids, query.as_deref(), yes).await,
+            SourceData::SetStatus { status, query, yes } => command::source_data::set_status(&mut context, &status, &query, yes).await,
+            SourceData::Analyse { paths } => command::source_data::analyse(&mut context, &paths).await,
+            SourceData::Download { site, status, hql, limit, since, max_attempts, offline, no_cache } => {
+                let cache_mode = if offline { CacheMode::Offline }else if no_cache { CacheMode::Refresh }else{ CacheMode::Prefer };
+                command::source_data::download(&mut context, &DownloadOptions { site, status, hql, limit, since, max_attempts, cache_mode }).await
+            },
+            SourceData::Connect { split, limit, update, verbose, vars, resume, since, dry_run } => command::source_data::connect(&mut context, &ConnectOptions { split, limit, update, verbose, vars, resume, since, dry_run }).await
+        }
+        Cli::Mapping(mapping) => match mapping {
+            Mapping::Get { site, tag_type, code } => command::mapping::get(&mut context, &site, &tag_type, &code).await,
+            Mapping::Set { site, tag_type, code, targets } => command::mapping::set(&mut context, &site, &tag_type, &code, &targets).await,
+            Mapping::Delete { site, tag_type, code } => command::mapping::delete(&mut context, &site, &tag_type, &code).await,
+            Mapping::Query { site, tags } => command::mapping::query(&mut context, &site, &tags).await,
+            Mapping::Export { site, output, unmapped } => command::mapping::export(&mut context, &site, output.as_ref(), unmapped).await,
+            Mapping::Unmapped { site, min_count, limit, output } => command::mapping::unmapped(&mut context, &site, min_count, limit, output.as_ref()).await
         }
         Cli::Tool(tool) => match tool {
-            Tool::ImportFolder { dir, tree, dry_run } => command::tool::import_folder(&mut context, &dir, &tree, dry_run).await
+            Tool::ImportFolder { dir, tree, dry_run } => command::tool::import_folder(&mut context, &dir, &tree, dry_run).await,
+            Tool::TestRules { rules, filenames } => command::tool::test_rules(&rules, &filenames)
+        }
+        Cli::Config(config) => match config {
+            Config::CheckConnect { split, rows, vars } => command::config::check_connect(&context, &split, rows, &vars)
         }
         Cli::Completion(completion) => generate(completion.shell, &mut Cli::command(), "hedge", &mut std::io::stdout())
     }
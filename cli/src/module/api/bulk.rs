@@ -1,7 +1,9 @@
 use std::error::Error;
 use reqwest::Method;
 use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
 use super::super::server::{ServerManager, ErrorResult};
+use crate::utils::deserialize::{string_or_integer, option_vec_string_or_integer, StringOrInteger};
 
 
 pub struct BulkModule<'t> {
@@ -48,10 +50,10 @@ pub struct SourceDataIdentity {
     #[serde(rename = "sourceSite")]
     pub source_site: String,
     #[serde(rename = "sourceId")]
-    pub source_id: i64
+    pub source_id: String
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct TagBulkForm {
     pub name: String,
@@ -77,7 +79,7 @@ pub struct TagBulkForm {
     pub children: Option<Vec<Box<TagBulkForm>>>
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct TopicBulkForm {
     pub name: String,
@@ -101,7 +103,7 @@ pub struct TopicBulkForm {
     pub children: Option<Vec<Box<TopicBulkForm>>>
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct AuthorBulkForm {
     pub name: String,
@@ -123,13 +125,14 @@ pub struct AuthorBulkForm {
     pub mapping_source_tags: Option<Vec<MappingSourceTagForm>>
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct SourceDataBulkForm {
     #[serde(rename = "sourceSite", alias = "source_site", alias = "site")]
     pub source_site: String,
-    #[serde(rename = "sourceId", alias = "source_id", alias = "id")]
-    pub source_id: i64,
+    #[serde(rename = "sourceId", alias = "source_id", alias = "id", deserialize_with = "string_or_integer")]
+    #[schemars(with = "StringOrInteger")]
+    pub source_id: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub status: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -140,15 +143,18 @@ pub struct SourceDataBulkForm {
     pub tags: Option<Vec<SourceTagForm>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub books: Option<Vec<SourceBookForm>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub relations: Option<Vec<i64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "option_vec_string_or_integer")]
+    #[schemars(with = "Option<Vec<StringOrInteger>>")]
+    pub relations: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub links: Option<Vec<String>>,
     #[serde(rename = "additionalInfo", alias = "additional_info", skip_serializing_if = "Option::is_none")] 
-    pub additional_info: Option<Vec<AdditionalInfoForm>>
+    pub additional_info: Option<Vec<AdditionalInfoForm>>,
+    #[serde(rename = "publishTime", alias = "publish_time", skip_serializing_if = "Option::is_none")]
+    pub publish_time: Option<String>
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct SourceTagForm {
     pub code: String,
@@ -160,7 +166,7 @@ pub struct SourceTagForm {
     pub tag_type: Option<String>
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct SourceBookForm {
     pub code: String,
@@ -170,14 +176,14 @@ pub struct SourceBookForm {
     pub other_title: Option<String>
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct AdditionalInfoForm {
     pub field: String,
     pub value: String
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct MappingSourceTagForm {
     pub site: String,
@@ -0,0 +1,157 @@
+use std::error::Error;
+use reqwest::Method;
+use serde::Deserialize;
+use crate::module::server::{ServerManager, ListResult};
+
+
+pub struct MetaModule<'t> {
+    server_manager: &'t ServerManager
+}
+
+impl <'t> MetaModule<'t> {
+    pub fn new(server_manager: &'t ServerManager) -> MetaModule<'t> {
+        MetaModule { server_manager }
+    }
+    pub async fn tag_tree(&mut self) -> Result<Vec<TagTreeNode>, Box<dyn Error>> {
+        self.server_manager.req(Method::GET, "/api/tags/tree").await
+    }
+    pub async fn get_tag(&mut self, id: i32) -> Result<TagDetailRes, Box<dyn Error>> {
+        self.server_manager.req(Method::GET, format!("/api/tags/{id}")).await
+    }
+    pub async fn list_topics(&mut self, offset: u32, limit: u32) -> Result<ListResult<TopicRes>, Box<dyn Error>> {
+        let query = vec![("offset", offset.to_string()), ("limit", limit.to_string())];
+        self.server_manager.req_with_query(Method::GET, "/api/topics", &query).await
+    }
+    pub async fn get_topic(&mut self, id: i32) -> Result<TopicDetailRes, Box<dyn Error>> {
+        self.server_manager.req(Method::GET, format!("/api/topics/{id}")).await
+    }
+    pub async fn list_authors(&mut self, offset: u32, limit: u32) -> Result<ListResult<AuthorRes>, Box<dyn Error>> {
+        let query = vec![("offset", offset.to_string()), ("limit", limit.to_string())];
+        self.server_manager.req_with_query(Method::GET, "/api/authors", &query).await
+    }
+    pub async fn get_author(&mut self, id: i32) -> Result<AuthorDetailRes, Box<dyn Error>> {
+        self.server_manager.req(Method::GET, format!("/api/authors/{id}")).await
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TagTreeNode {
+    pub id: i32,
+    pub name: String,
+    // #[serde(rename = "otherNames")]
+    // pub other_names: Vec<String>,
+    // #[serde(rename = "type")]
+    // pub tag_type: String,
+    // #[serde(rename = "isSequenceGroup")]
+    // pub is_sequence_group: bool,
+    // #[serde(rename = "isOverrideGroup")]
+    // pub is_override_group: bool,
+    // pub color: Option<String>,
+    pub children: Option<Vec<TagTreeNode>>
+}
+
+#[derive(Deserialize)]
+pub struct TagDetailRes {
+    // pub id: i32,
+    pub name: String,
+    #[serde(rename = "otherNames")]
+    pub other_names: Vec<String>,
+    #[serde(rename = "type")]
+    pub tag_type: String,
+    #[serde(rename = "isSequenceGroup")]
+    pub is_sequence_group: bool,
+    #[serde(rename = "isOverrideGroup")]
+    pub is_override_group: bool,
+    pub links: Vec<TagLink>,
+    pub description: String,
+    pub color: Option<String>,
+    #[serde(rename = "mappingSourceTags")]
+    pub mapping_source_tags: Vec<MappingSourceTagDto>
+}
+
+#[derive(Deserialize)]
+pub struct TagLink {
+    pub id: i32,
+    // pub name: String
+}
+
+#[derive(Deserialize)]
+pub struct TopicRes {
+    pub id: i32,
+    pub name: String,
+    #[serde(rename = "parentRoot")]
+    pub parent_root: Option<TopicParent>,
+    #[serde(rename = "parentId")]
+    pub parent_id: Option<i32>,
+    #[serde(rename = "type")]
+    pub tag_type: String
+}
+
+#[derive(Deserialize)]
+pub struct TopicParent {
+    pub id: i32,
+    // pub name: String,
+    // #[serde(rename = "type")]
+    // pub tag_type: String
+}
+
+#[derive(Deserialize)]
+pub struct TopicDetailRes {
+    // pub id: i32,
+    pub name: String,
+    pub children: Option<Vec<TopicChildrenNode>>,
+    #[serde(rename = "otherNames")]
+    pub other_names: Vec<String>,
+    pub keywords: Vec<String>,
+    pub description: String,
+    #[serde(rename = "type")]
+    pub tag_type: String,
+    pub favorite: bool,
+    pub score: Option<i32>,
+    #[serde(rename = "mappingSourceTags")]
+    pub mapping_source_tags: Vec<MappingSourceTagDto>
+}
+
+#[derive(Deserialize)]
+pub struct TopicChildrenNode {
+    pub id: i32,
+    // pub name: String,
+    // #[serde(rename = "type")]
+    // pub tag_type: String,
+    // pub children: Option<Vec<TopicChildrenNode>>
+}
+
+#[derive(Deserialize)]
+pub struct AuthorRes {
+    pub id: i32,
+    pub name: String,
+    // #[serde(rename = "type")]
+    // pub tag_type: String
+}
+
+#[derive(Deserialize)]
+pub struct AuthorDetailRes {
+    // pub id: i32,
+    pub name: String,
+    #[serde(rename = "otherNames")]
+    pub other_names: Vec<String>,
+    pub keywords: Vec<String>,
+    pub description: String,
+    #[serde(rename = "type")]
+    pub tag_type: String,
+    pub favorite: bool,
+    pub score: Option<i32>,
+    #[serde(rename = "mappingSourceTags")]
+    pub mapping_source_tags: Vec<MappingSourceTagDto>
+}
+
+#[derive(Deserialize)]
+pub struct MappingSourceTagDto {
+    pub site: String,
+    #[serde(rename = "type")]
+    pub tag_type: String,
+    pub code: String,
+    pub name: Option<String>,
+    #[serde(rename = "otherName")]
+    pub other_name: Option<String>
+}
@@ -1,5 +1,7 @@
 pub mod source_data;
+pub mod source_tag_mapping;
 pub mod bulk;
 pub mod setting;
 pub mod folder;
-pub mod log;
\ No newline at end of file
+pub mod log;
+pub mod meta;
\ No newline at end of file
@@ -1,6 +1,7 @@
 use std::{error::Error, collections::HashMap};
 use reqwest::Method;
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 use crate::module::server::ServerManager;
 
 
@@ -33,6 +34,17 @@ impl <'t> SettingModule<'t> {
     // pub async fn _get_source_sites(&mut self) -> Result<Vec<SourceSite>, Box<dyn Error>> {
     //     self.server_manager.req(Method::GET, "/api/setting/source/sites").await
     // }
+    /// 以原始JSON取得一项设置。name为设置的路径名，如server、storage、find-similar。
+    pub async fn get_option(&mut self, name: &str) -> Result<serde_json::Value, Box<dyn Error>> {
+        self.server_manager.req(Method::GET, format!("/api/setting/{name}")).await
+    }
+    /// 以原始JSON取得全部来源站点。
+    pub async fn get_source_sites_value(&mut self) -> Result<Vec<serde_json::Value>, Box<dyn Error>> {
+        self.server_manager.req(Method::GET, "/api/setting/source/sites").await
+    }
+    pub async fn get_source_site(&mut self, name: &str) -> Result<SourceSite, Box<dyn Error>> {
+        self.server_manager.req(Method::GET, format!("/api/setting/source/sites/{name}")).await
+    }
     pub async fn set_server_option(&mut self, bulks: &ServerOptionUpdateForm) -> Result<(), Box<dyn Error>> {
         let body = serde_json::to_value(bulks)?;
         self.server_manager.req_without_res(Method::PATCH, "/api/setting/server", body).await
@@ -155,25 +167,25 @@ impl <'t> SettingModule<'t> {
 //     pub source_analyse_rules: Vec<SourceAnalyseRule>
 // }
 
-// #[derive(Deserialize)]
-// pub struct SourceSite {
-//     pub name: String,
-//     pub title: String,
-//     #[serde(rename = "idMode")]
-//     pub id_mode: String,
-//     #[serde(rename = "partMode")]
-//     pub part_mode: String,
-//     #[serde(rename = "additionalInfo")]
-//     pub additional_info: Vec<AdditionalInfo>,
-//     #[serde(rename = "sourceLinkRules")]
-//     pub source_link_rules: Vec<String>,
-//     #[serde(rename = "tagTypes")]
-//     pub tag_types: Vec<String>,
-//     #[serde(rename = "tagTypeMappings")]
-//     pub tag_type_mappings: Vec<HashMap<String, String>>
-// }
+#[derive(Deserialize)]
+pub struct SourceSite {
+    // pub name: String,
+    // pub title: String,
+    // #[serde(rename = "idMode")]
+    // pub id_mode: String,
+    // #[serde(rename = "partMode")]
+    // pub part_mode: String,
+    // #[serde(rename = "additionalInfo")]
+    // pub additional_info: Vec<AdditionalInfo>,
+    // #[serde(rename = "sourceLinkRules")]
+    // pub source_link_rules: Vec<String>,
+    // #[serde(rename = "tagTypes")]
+    // pub tag_types: Vec<String>,
+    #[serde(rename = "tagTypeMappings")]
+    pub tag_type_mappings: HashMap<String, String>
+}
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct ServerOptionUpdateForm {
     #[serde(rename = "port", skip_serializing_if = "Option::is_none")]
@@ -184,7 +196,7 @@ pub struct ServerOptionUpdateForm {
     pub time_offset_hour: Option<i32>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct StorageOptionUpdateForm {
     #[serde(rename = "storagePath", skip_serializing_if = "Option::is_none")]
@@ -199,7 +211,7 @@ pub struct StorageOptionUpdateForm {
     pub block_max_count: Option<i32>
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct FindSimilarOptionUpdateForm {
     #[serde(rename = "autoFindSimilar", alias = "auto_find_similar", skip_serializing_if = "Option::is_none")]
@@ -210,7 +222,7 @@ pub struct FindSimilarOptionUpdateForm {
     pub default_task_conf: Option<FindSimilarTaskConfig>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct QueryOptionUpdateForm {
     #[serde(rename = "chineseSymbolReflect", alias = "chinese_symbol_reflect", skip_serializing_if = "Option::is_none")]
@@ -225,7 +237,7 @@ pub struct QueryOptionUpdateForm {
     pub warning_limit_of_intersect_items: Option<i32>
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct MetaOptionUpdateForm {
     #[serde(rename = "autoCleanTagme", alias = "auto_clean_tagme", skip_serializing_if = "Option::is_none")]
@@ -248,7 +260,7 @@ pub struct MetaOptionUpdateForm {
     pub author_colors: Option<HashMap<String, String>>
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct ImportOptionUpdateForm {
     #[serde(rename = "autoAnalyseSourceData", alias = "auto_analyse_source_data", skip_serializing_if = "Option::is_none")]
@@ -273,7 +285,7 @@ pub struct ImportOptionUpdateForm {
     pub source_analyse_rules: Option<Vec<SourceAnalyseRule>>
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct SourceSiteUpdateForm {
     pub name: String,
@@ -293,19 +305,27 @@ pub struct SourceSiteUpdateForm {
     pub tag_type_mappings: Option<HashMap<String, String>>
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct FindSimilarTaskConfig {
     #[serde(rename = "findBySourceIdentity", alias = "find_by_source_identity")]
     pub find_by_source_identity: bool,
+    #[serde(rename = "findBySourcePart", alias = "find_by_source_part")]
+    pub find_by_source_part: bool,
     #[serde(rename = "findBySourceRelation", alias = "find_by_source_relation")]
     pub find_by_source_relation: bool,
-    #[serde(rename = "findBySourceMark", alias = "find_by_source_mark")]
-    pub find_by_source_mark: bool,
+    #[serde(rename = "findBySourceBook", alias = "find_by_source_book")]
+    pub find_by_source_book: bool,
     #[serde(rename = "findBySimilarity", alias = "find_by_similarity")]
     pub find_by_similarity: bool,
-    #[serde(rename = "filterByOtherImport", alias = "filter_by_other_import")]
-    pub filter_by_other_import: bool,
+    #[serde(rename = "filterInCurrentScope", alias = "filter_in_current_scope")]
+    pub filter_in_current_scope: bool,
+    #[serde(rename = "filterBySourcePart", alias = "filter_by_source_part")]
+    pub filter_by_source_part: bool,
+    #[serde(rename = "filterBySourceBook", alias = "filter_by_source_book")]
+    pub filter_by_source_book: bool,
+    #[serde(rename = "filterBySourceRelation", alias = "filter_by_source_relation")]
+    pub filter_by_source_relation: bool,
     #[serde(rename = "filterByPartition", alias = "filter_by_partition")]
     pub filter_by_partition: bool,
     #[serde(rename = "filterByTopic", alias = "filter_by_topic")]
@@ -316,7 +336,7 @@ pub struct FindSimilarTaskConfig {
     pub filter_by_source_tag_type: Vec<FindSimilarTaskConfigSourceTagType>
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct FindSimilarTaskConfigSourceTagType {
     #[serde(rename = "sourceSite", alias = "source_site")]
@@ -325,7 +345,7 @@ pub struct FindSimilarTaskConfigSourceTagType {
     pub tag_type: String
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct SourceAnalyseRule {
     pub site: String,
@@ -339,7 +359,7 @@ pub struct SourceAnalyseRule {
     pub extras: Option<Vec<SourceAnalyseRuleExtra>>
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct SourceAnalyseRuleExtra {
     pub group: String,
@@ -353,7 +373,7 @@ pub struct SourceAnalyseRuleExtra {
     pub translate_underscore_to_space: Option<bool>
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct AdditionalInfo {
     pub field: String,
@@ -1,8 +1,9 @@
 use std::error::Error;
+use clap::ValueEnum;
 use reqwest::Method;
 use serde::{Serialize, Deserialize};
 use serde_json::json;
-// use crate::module::import::SourceDataPath;
+use crate::module::import::SourceDataPath;
 use crate::module::server::{ServerManager, ListResult};
 
 
@@ -14,11 +15,12 @@ impl <'t> SourceDataModule<'t> {
     pub fn new(server_manager: &'t ServerManager) -> SourceDataModule<'t> {
         SourceDataModule { server_manager }        
     }
-    pub async fn query(&mut self, hql: Option<&str>, status: Option<Vec<&str>>, site: Option<Vec<&str>>, offset: Option<u32>, limit: Option<u32>) -> Result<ListResult<SourceDataRes>, Box<dyn Error>> {
+    pub async fn query(&mut self, hql: Option<&str>, status: Option<Vec<&str>>, site: Option<Vec<&str>>, order: Option<&str>, offset: Option<u32>, limit: Option<u32>) -> Result<ListResult<SourceDataRes>, Box<dyn Error>> {
         let mut query = Vec::new();
         if let Some(hql) = hql { query.push(("query", hql.to_string())) }
         if let Some(status) = status { query.push(("status", status.join(","))) }
         if let Some(site) = site { query.push(("site", site.join(","))) }
+        if let Some(order) = order { query.push(("order", order.to_string())) }
         if let Some(limit) = limit { query.push(("limit", limit.to_string())) }
         if let Some(offset) = offset { query.push(("offset", offset.to_string())) }
         self.server_manager.req_with_query(Method::GET, "/api/source-data", &query).await
@@ -26,20 +28,24 @@ impl <'t> SourceDataModule<'t> {
     pub async fn get(&mut self, source_site: &str, source_id: &str) -> Result<SourceDataDetailRes, Box<dyn Error>> {
         self.server_manager.req(Method::GET, format!("/api/source-data/{source_site}/{source_id}")).await
     }
-    pub async fn create(&mut self, source_site: &str, source_id: &str, form: &SourceDataUpdateForm) -> Result<(), Box<dyn Error>> {
-        let mut body = serde_json::to_value(form)?;
-        body["sourceSite"] = json!(source_site);
-        body["sourceId"] = json!(source_id);
-        self.server_manager.req_without_res(Method::POST, format!("/api/source-data"), body).await
+    pub async fn get_related_images(&mut self, source_site: &str, source_id: &str) -> Result<Vec<IllustSimpleRes>, Box<dyn Error>> {
+        self.server_manager.req(Method::GET, format!("/api/source-data/{source_site}/{source_id}/related-images")).await
     }
     pub async fn update(&mut self, source_site: &str, source_id: &str, form: &SourceDataUpdateForm) -> Result<(), Box<dyn Error>> {
         let body = serde_json::to_value(form)?;
         self.server_manager.req_without_res(Method::PATCH, format!("/api/source-data/{source_site}/{source_id}"), body).await
     }
+    pub async fn delete(&mut self, source_site: &str, source_id: &str) -> Result<(), Box<dyn Error>> {
+        self.server_manager.req_without_res(Method::DELETE, format!("/api/source-data/{source_site}/{source_id}"), json!(null)).await
+    }
     pub async fn analyse_source_name(&mut self, filenames: &Vec<&str>) -> Result<Vec<SourceDataAnalyseResult>, Box<dyn Error>> {
         let body = serde_json::to_value(filenames)?;
         self.server_manager.req_with_body(Method::POST, format!("/api/source-data/analyse-name"), body).await
     }
+    pub async fn collect_status(&mut self, paths: &Vec<SourceDataPath>) -> Result<Vec<SourceDataCollectStatus>, Box<dyn Error>> {
+        let body = serde_json::to_value(paths)?;
+        self.server_manager.req_with_body(Method::POST, "/api/source-data/collect-status", body).await
+    }
 }
 
 #[derive(Deserialize)]
@@ -58,69 +64,105 @@ pub struct SourceDataRes {
     pub relation_count: i32,
     pub status: String,
     // pub empty: bool,
-    // #[serde(rename = "createTime")]
-    // pub create_time: String,
+    #[serde(rename = "createTime")]
+    pub create_time: String,
     // #[serde(rename = "updateTime")]
     // pub update_time: String
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct SourceDataDetailRes {
-    // #[serde(rename = "sourceSite")]
-    // pub site: String,
-    // #[serde(rename = "sourceSiteName")]
-    // pub site_name: String,
-    // #[serde(rename = "sourceId")]
-    // pub source_id: i64,
-    // pub title: String,
-    // pub description: String,
-    // pub tags: Vec<SourceTagDto>,
-    // pub books: Vec<SourceBookDto>,
-    // pub relations: Vec<i64>,
-    // pub links: Vec<String>,
-    // #[serde(rename = "additionalInfo")] 
-    // pub additional_info: Vec<AdditionalInfoDto>,
-    // pub empty: bool,
+    #[serde(rename = "sourceSite")]
+    pub site: String,
+    #[serde(rename = "sourceSiteName")]
+    pub site_name: String,
+    #[serde(rename = "sourceId")]
+    pub source_id: String,
+    pub title: String,
+    pub description: String,
+    pub tags: Vec<SourceTagDto>,
+    pub books: Vec<SourceBookDto>,
+    pub relations: Vec<String>,
+    pub links: Vec<String>,
+    #[serde(rename = "additionalInfo")] 
+    pub additional_info: Vec<AdditionalInfoDto>,
+    pub empty: bool,
     pub status: String,
-    // #[serde(rename = "createTime")]
-    // pub create_time: String,
-    // #[serde(rename = "updateTime")]
-    // pub update_time: String
+    #[serde(rename = "publishTime")]
+    pub publish_time: Option<String>,
+    #[serde(rename = "createTime")]
+    pub create_time: String,
+    #[serde(rename = "updateTime")]
+    pub update_time: String
 }
 
 #[derive(Deserialize)]
 pub struct SourceDataAnalyseResult {
-    // pub filename: String,
-    // pub source: Option<SourceDataPath>,
+    pub filename: String,
+    pub source: Option<SourceDataPath>,
     #[serde(rename = "imageId")] 
     pub image_id: Option<i32>,
     pub error: Option<String>
 }
 
-// #[derive(Deserialize)]
-// pub struct SourceTagDto {
-//     pub code: String,
-//     pub name: String,
-//     #[serde(rename = "otherName")]
-//     pub other_name: Option<String>,
-//     #[serde(rename = "type")]
-//     pub tag_type: Option<String>
-// }
-
-// #[derive(Deserialize)]
-// pub struct SourceBookDto {
-//     pub code: String,
-//     pub title: String,
-//     #[serde(rename = "otherTitle")]
-//     pub other_title: Option<String>
-// }
-
-// #[derive(Deserialize)]
-// pub struct AdditionalInfoDto {
-//     pub field: String,
-//     pub label: String,
-//     pub value: String
-// }
+#[derive(Deserialize, Serialize)]
+pub struct SourceTagDto {
+    pub code: String,
+    #[serde(rename = "type")]
+    pub tag_type: String,
+    pub name: Option<String>,
+    #[serde(rename = "otherName")]
+    pub other_name: Option<String>
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct SourceBookDto {
+    pub code: String,
+    pub title: String,
+    #[serde(rename = "otherTitle")]
+    pub other_title: Option<String>
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct AdditionalInfoDto {
+    pub field: String,
+    pub label: String,
+    pub value: String
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct IllustSimpleRes {
+    pub id: i32,
+    #[serde(rename = "filePath")]
+    pub file_path: FilePath
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct FilePath {
+    pub original: String,
+    pub thumbnail: String,
+    pub sample: String,
+    pub extension: String
+}
+
+#[derive(Clone, ValueEnum)]
+pub enum SourceEditStatus {
+    NotEdited,
+    Edited,
+    Error,
+    Ignored
+}
+
+impl SourceEditStatus {
+    pub fn to_json_code(&self) -> &'static str {
+        match self {
+            Self::NotEdited => "NOT_EDITED",
+            Self::Edited => "EDITED",
+            Self::Error => "ERROR",
+            Self::Ignored => "IGNORED"
+        }
+    }
+}
 
 #[derive(Serialize)]
 pub struct SourceDataUpdateForm {
@@ -165,3 +207,17 @@ pub struct AdditionalInfoForm {
     pub field: String,
     pub value: String
 }
+
+#[derive(Deserialize)]
+pub struct SourceDataCollectStatus {
+    pub source: SourceDataPath,
+    // #[serde(rename = "imageCount")]
+    // pub image_count: i32,
+    // #[serde(rename = "imageInDiffIdCount")]
+    // pub image_in_diff_id_count: i32,
+    // pub collected: bool,
+    #[serde(rename = "collectStatus")]
+    pub collect_status: Option<String>,
+    // #[serde(rename = "collectTime")]
+    // pub collect_time: Option<String>
+}
@@ -0,0 +1,91 @@
+use std::error::Error;
+use reqwest::Method;
+use serde::{Serialize, Deserialize};
+use serde_json::json;
+use crate::module::server::ServerManager;
+use super::source_data::SourceTagDto;
+
+
+pub struct SourceTagMappingModule<'t> {
+    server_manager: &'t ServerManager
+}
+
+impl <'t> SourceTagMappingModule<'t> {
+    pub fn new(server_manager: &'t ServerManager) -> SourceTagMappingModule<'t> {
+        SourceTagMappingModule { server_manager }
+    }
+    pub async fn batch_query(&mut self, paths: &Vec<SourceTagPath>) -> Result<Vec<SourceMappingBatchQueryResult>, Box<dyn Error>> {
+        let body = serde_json::to_value(paths)?;
+        self.server_manager.req_with_body(Method::POST, "/api/source-tag-mappings/batch-query", body).await
+    }
+    pub async fn get(&mut self, site: &str, tag_type: &str, code: &str) -> Result<Vec<SourceMappingTargetItemDetail>, Box<dyn Error>> {
+        self.server_manager.req(Method::GET, mapping_path(site, tag_type, code)).await
+    }
+    pub async fn update(&mut self, site: &str, tag_type: &str, code: &str, items: &Vec<SourceMappingTargetItem>) -> Result<(), Box<dyn Error>> {
+        let body = serde_json::to_value(items)?;
+        self.server_manager.req_without_res(Method::PUT, mapping_path(site, tag_type, code), body).await
+    }
+    pub async fn delete(&mut self, site: &str, tag_type: &str, code: &str) -> Result<(), Box<dyn Error>> {
+        self.server_manager.req_without_res(Method::DELETE, mapping_path(site, tag_type, code), json!(null)).await
+    }
+}
+
+/// 来源标签的code可能包含/、?等字符，因此需要对路径片段做百分号编码。
+fn mapping_path(site: &str, tag_type: &str, code: &str) -> String {
+    format!("/api/source-tag-mappings/{}/{}/{}", encode_path_segment(site), encode_path_segment(tag_type), encode_path_segment(code))
+}
+
+fn encode_path_segment(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b == b'-' || b == b'_' || b == b'.' || b == b'~' {
+            ret.push(b as char);
+        }else{
+            ret.push_str(&format!("%{:02X}", b));
+        }
+    }
+    ret
+}
+
+#[derive(Serialize)]
+pub struct SourceTagPath {
+    #[serde(rename = "sourceSite")]
+    pub source_site: String,
+    #[serde(rename = "sourceTagType")]
+    pub source_tag_type: String,
+    #[serde(rename = "sourceTagCode")]
+    pub source_tag_code: String
+}
+
+#[derive(Deserialize)]
+pub struct SourceMappingBatchQueryResult {
+    // pub site: String,
+    #[serde(rename = "type")]
+    pub tag_type: String,
+    pub code: String,
+    #[serde(rename = "sourceTag")]
+    pub source_tag: SourceTagDto,
+    pub mappings: Vec<SourceMappingTargetItemDetail>
+}
+
+#[derive(Deserialize)]
+pub struct SourceMappingTargetItemDetail {
+    #[serde(rename = "metaType")]
+    pub meta_type: String,
+    #[serde(rename = "metaTag")]
+    pub meta_tag: MetaTagSimpleRes
+}
+
+#[derive(Deserialize)]
+pub struct MetaTagSimpleRes {
+    pub id: i32,
+    pub name: String
+}
+
+#[derive(Serialize)]
+pub struct SourceMappingTargetItem {
+    #[serde(rename = "metaType")]
+    pub meta_type: String,
+    #[serde(rename = "metaId")]
+    pub meta_id: i32
+}
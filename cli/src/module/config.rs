@@ -45,9 +45,10 @@ pub fn load_config() -> LocalConfig {
         connect: data.connect.map(|c| Connect { 
             driver: c.driver, 
             url: c.url, 
-            query: c.query, 
+            query: c.query.unwrap_or_default(), 
             parser: c.parser
-        })
+        }),
+        merge: data.merge.unwrap_or_default()
     }
 }
 
@@ -76,7 +77,8 @@ fn default_config_file() -> LocalConfigFile {
             userdata_path: Option::None
         },
         download: Option::None,
-        connect: Option::None
+        connect: Option::None,
+        merge: Option::None
     }
 }
 
@@ -84,7 +86,8 @@ pub struct LocalConfig {
     pub debug_mode: bool,
     pub work_path: WorkPath,
     pub download: Download,
-    pub connect: Option<Connect>
+    pub connect: Option<Connect>,
+    pub merge: MergePolicy
 }
 
 pub struct WorkPath {
@@ -108,10 +111,40 @@ pub struct Connect {
     pub parser: HashMap<String, ConnectParser>
 }
 
+/// 更新已存在的来源数据时，各字段的合并策略。未配置的字段使用replace。
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct MergePolicy {
+    pub title: MergeStrategy,
+    pub description: MergeStrategy,
+    pub tags: MergeStrategy,
+    pub books: MergeStrategy,
+    pub relations: MergeStrategy,
+    pub additional_info: MergeStrategy
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum MergeStrategy {
+    /// 以新的值覆盖现有的值
+    #[default]
+    Replace,
+    /// 现有的值非空时保留现有的值
+    KeepExisting,
+    /// 保留现有的项，追加不存在的新项。用于title/description时等同于prefer-non-empty
+    Union,
+    /// 新的值非空时才覆盖现有的值
+    PreferNonEmpty
+}
+
 #[derive(Deserialize, Clone)]
 pub struct AvailableSite {
     pub site: String,
-    pub rule: String
+    pub rule: String,
+    pub rate: Option<u32>,
+    pub burst: Option<u32>,
+    pub backoff: Option<u64>,
+    pub error_field: Option<String>
 }
 
 #[derive(Deserialize)]
@@ -119,7 +152,8 @@ struct LocalConfigFile {
     debug_mode: Option<bool>,
     work_path: LocalConfigFileWorkPath,
     download: Option<LocalConfigFileDownload>,
-    connect: Option<LocalConfigDbConnect>
+    connect: Option<LocalConfigDbConnect>,
+    merge: Option<MergePolicy>
 }
 
 #[derive(Deserialize)]
@@ -142,7 +176,7 @@ struct LocalConfigFileDownload {
 struct LocalConfigDbConnect {
     driver: String,
     url: String,
-    query: String,
+    query: Option<String>,
     parser: HashMap<String, ConnectParser>
 }
 
@@ -155,7 +189,11 @@ pub struct ConnectParser {
     pub book: Option<ConnectParserBook>,
     pub relation: Option<ConnectParserRelation>,
     pub additional_info: Option<HashMap<String, String>>,
-    pub translate_underscore_to_space: Option<Vec<String>>
+    pub translate_underscore_to_space: Option<Vec<String>>,
+    pub rewrite: Option<Vec<ConnectParserRewrite>>,
+    pub tag_type_map: Option<HashMap<String, String>>,
+    pub tag_blocklist: Option<Vec<String>>,
+    pub tag_type_blocklist: Option<Vec<String>>
 }
 
 #[derive(Deserialize, Clone)]
@@ -179,4 +217,11 @@ pub struct ConnectParserBook {
 #[derive(Deserialize, Clone)]
 pub struct ConnectParserRelation {
     pub selector: Vec<String>
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ConnectParserRewrite {
+    pub pattern: String,
+    pub replacement: String,
+    pub fields: Option<Vec<String>>
 }
\ No newline at end of file
@@ -0,0 +1,241 @@
+use std::{error::Error, cmp::Ordering, collections::{HashMap, VecDeque}, fs::File, io::{BufRead, BufReader, Lines}};
+use crate::utils::error::ApplicationError;
+use super::{Driver, Rows, Row, ColumnValue, QueryParam, SITE_COLUMNS, find_column};
+
+
+/// 文件driver。每条记录被视作一行，记录的顶层字段被视作列，因此parser的选择器可以直接寻址字段。
+/// url中的$split会被替换为split的值，以便每个split对应一个文件；url中没有$split时只能给出一个split。文件driver不使用query，而是按参数过滤记录:
+/// 只读取site已配置的记录；记录没有key字段时，以记录在文件中的序号(从1开始)作为key。
+pub struct FileDriver {
+    url: String,
+    format: FileFormat
+}
+
+pub enum FileFormat {
+    JsonLines,
+    Csv
+}
+
+struct FileRows {
+    readers: VecDeque<RecordReader>,
+    pending: Option<Result<HashMap<String, ColumnValue>, Box<dyn Error>>>,
+    columns: Vec<String>,
+    site_col_name: Option<String>,
+    sites: Vec<String>,
+    synthetic_key: bool,
+    cursor: ColumnValue,
+    limit: Option<usize>,
+    count: usize,
+    record_no: i64
+}
+
+enum RecordReader {
+    JsonLines(Lines<BufReader<File>>),
+    Csv(csv::StringRecordsIntoIter<File>, Vec<String>)
+}
+
+impl FileDriver {
+    pub fn new(url: &str, format: FileFormat) -> FileDriver {
+        FileDriver { url: url.to_string(), format }
+    }
+}
+
+impl Driver for FileDriver {
+    fn splittable(&self) -> bool {
+        self.url.contains("$split")
+    }
+    fn query<'s>(&'s mut self, _query: &str, params: &HashMap<&str, QueryParam>, limit: Option<u32>) -> Result<Box<dyn Rows + 's>, Box<dyn Error>> {
+        let paths: Vec<String> = match params.get("split") {
+            Some(QueryParam::List(splits)) if self.url.contains("$split") => splits.iter().map(|s| self.url.replace("$split", s)).collect(),
+            _ => vec![self.url.clone()]
+        };
+        let sites = if let Some(QueryParam::List(l)) = params.get("site") { l.clone() }else{ Vec::new() };
+        //文件没有query可以书写$limit，因此只在给出--limit时限制数量，而不使用$limit的默认值
+        let limit = limit.map(|n| n as usize);
+        let cursor = if let Some(QueryParam::Value(v)) = params.get("cursor") { v.clone() }else{ ColumnValue::Null };
+
+        let mut readers = VecDeque::with_capacity(paths.len());
+        for path in paths.iter() {
+            readers.push_back(RecordReader::open(path, &self.format).map_err(|e| ApplicationError::new(&format!("Cannot open {}: {}", path, e)))?);
+        }
+
+        //以第一条记录的字段作为列
+        let mut pending = Option::None;
+        while let Some(reader) = readers.front_mut() {
+            if let Some(record) = reader.next() {
+                pending = Option::Some(record);
+                break
+            }
+            readers.pop_front();
+        }
+        let mut columns: Vec<String> = match &pending {
+            Some(Ok(record)) => record.keys().cloned().collect(),
+            Some(Err(_)) | None => Vec::new()
+        };
+        if let Some(RecordReader::Csv(_, headers)) = readers.front() {
+            columns = headers.clone();
+        }
+        let synthetic_key = !columns.iter().any(|c| c == "key");
+        if synthetic_key {
+            columns.push("key".to_string());
+        }
+        let site_col_name = find_column(&columns, &SITE_COLUMNS);
+
+        Result::Ok(Box::new(FileRows { readers, pending, columns, site_col_name, sites, synthetic_key, cursor, limit, count: 0, record_no: 0 }))
+    }
+}
+
+impl Rows for FileRows {
+    fn columns(&self) -> &[String] {
+        &self.columns
+    }
+    fn next_row(&mut self) -> Option<Result<Row, Box<dyn Error>>> {
+        loop {
+            if let Some(limit) = self.limit {
+                if self.count >= limit { return Option::None }
+            }
+            let record = if let Some(record) = self.pending.take() { record }else{
+                let reader = self.readers.front_mut()?;
+                match reader.next() {
+                    Some(record) => record,
+                    None => {
+                        self.readers.pop_front();
+                        self.record_no = 0;
+                        continue
+                    }
+                }
+            };
+            self.record_no += 1;
+            let mut values = match record {
+                Ok(values) => values,
+                Err(e) => return Option::Some(Result::Err(Box::new(ApplicationError::new(&format!("Record {} parse error: {}", self.record_no, e)))))
+            };
+            if values.is_empty() { continue }
+            if self.synthetic_key {
+                values.insert("key".to_string(), ColumnValue::Integer(self.record_no));
+            }
+            for column in self.columns.iter() {
+                if !values.contains_key(column) {
+                    values.insert(column.clone(), ColumnValue::Null);
+                }
+            }
+            if let Some(key) = values.get("key") {
+                if compare_value(key, &self.cursor).map(|o| o != Ordering::Greater).unwrap_or(false) { continue }
+            }
+            if let Some(site_col_name) = &self.site_col_name {
+                if let Some(ColumnValue::Text(site)) = values.get(site_col_name) {
+                    if !self.sites.contains(site) { continue }
+                }
+            }
+            self.count += 1;
+            return Option::Some(Result::Ok(Row { values }))
+        }
+    }
+    fn recoverable(&self) -> bool {
+        true
+    }
+}
+
+impl RecordReader {
+    fn open(path: &str, format: &FileFormat) -> Result<RecordReader, Box<dyn Error>> {
+        match format {
+            FileFormat::JsonLines => Result::Ok(RecordReader::JsonLines(BufReader::new(File::open(path)?).lines())),
+            FileFormat::Csv => {
+                let mut reader = csv::Reader::from_path(path)?;
+                let headers = reader.headers()?.iter().map(|h| h.to_string()).collect();
+                Result::Ok(RecordReader::Csv(reader.into_records(), headers))
+            }
+        }
+    }
+    fn next(&mut self) -> Option<Result<HashMap<String, ColumnValue>, Box<dyn Error>>> {
+        match self {
+            RecordReader::JsonLines(lines) => {
+                let line = match lines.next()? {
+                    Ok(line) => line,
+                    Err(e) => return Option::Some(Result::Err(Box::new(e)))
+                };
+                //空行返回空记录而不是跳过，使其仍然计入记录序号，以便序号与行号对应
+                if line.trim().is_empty() {
+                    return Option::Some(Result::Ok(HashMap::new()))
+                }
+                Option::Some(parse_json_record(&line))
+            },
+            RecordReader::Csv(records, headers) => {
+                let record = match records.next()? {
+                    Ok(record) => record,
+                    Err(e) => return Option::Some(Result::Err(Box::new(e)))
+                };
+                //CSV没有NULL，空字段被视作NULL
+                Option::Some(Result::Ok(headers.iter().cloned().zip(record.iter().map(|f| if f.is_empty() { ColumnValue::Null }else{ ColumnValue::Text(f.to_string()) })).collect()))
+            }
+        }
+    }
+}
+
+/// JSON记录的字符串、数字字段直接作为列值，对象、数组字段以JSON文本作为列值，交给选择器继续选择。
+fn parse_json_record(line: &str) -> Result<HashMap<String, ColumnValue>, Box<dyn Error>> {
+    let value: serde_json::Value = serde_json::from_str(line)?;
+    let serde_json::Value::Object(map) = value else {
+        return Result::Err(Box::new(ApplicationError::new("Record is not a JSON object.")))
+    };
+    Result::Ok(map.into_iter().map(|(k, v)| {
+        let v = match v {
+            serde_json::Value::Null => ColumnValue::Null,
+            serde_json::Value::String(s) => ColumnValue::Text(s),
+            serde_json::Value::Number(n) => if let Some(i) = n.as_i64() { ColumnValue::Integer(i) }else{ ColumnValue::Float(n.as_f64().unwrap_or_default()) },
+            v => ColumnValue::Text(v.to_string())
+        };
+        (k, v)
+    }).collect())
+}
+
+/// 比较两个列值。CSV的字段都是文本，因此两侧都能解析为数字时按数字比较，否则"10"会排在"9"之前。
+fn compare_value(a: &ColumnValue, b: &ColumnValue) -> Option<Ordering> {
+    match (to_number(a), to_number(b)) {
+        (Some(ColumnValue::Integer(a)), Some(ColumnValue::Integer(b))) => Option::Some(a.cmp(&b)),
+        (Some(ColumnValue::Integer(a)), Some(ColumnValue::Float(b))) => (a as f64).partial_cmp(&b),
+        (Some(ColumnValue::Float(a)), Some(ColumnValue::Integer(b))) => a.partial_cmp(&(b as f64)),
+        (Some(ColumnValue::Float(a)), Some(ColumnValue::Float(b))) => a.partial_cmp(&b),
+        _ => match (a, b) {
+            (ColumnValue::Text(a), ColumnValue::Text(b)) => Option::Some(a.cmp(b)),
+            _ => Option::None
+        }
+    }
+}
+
+fn to_number(v: &ColumnValue) -> Option<ColumnValue> {
+    match v {
+        ColumnValue::Integer(i) => Option::Some(ColumnValue::Integer(*i)),
+        ColumnValue::Float(f) => Option::Some(ColumnValue::Float(*f)),
+        ColumnValue::Text(s) => {
+            let s = s.trim();
+            if let Ok(i) = s.parse::<i64>() { Option::Some(ColumnValue::Integer(i)) }else{ s.parse::<f64>().ok().filter(|f| f.is_finite()).map(ColumnValue::Float) }
+        },
+        ColumnValue::Null => Option::None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> ColumnValue {
+        ColumnValue::Text(s.to_string())
+    }
+
+    #[test]
+    fn compare_numeric_text() {
+        assert_eq!(compare_value(&text("10"), &text("9")), Option::Some(Ordering::Greater));
+        assert_eq!(compare_value(&text("10"), &ColumnValue::Integer(9)), Option::Some(Ordering::Greater));
+        assert_eq!(compare_value(&text("1.5"), &ColumnValue::Integer(2)), Option::Some(Ordering::Less));
+        assert_eq!(compare_value(&ColumnValue::Integer(3), &ColumnValue::Integer(3)), Option::Some(Ordering::Equal));
+    }
+
+    #[test]
+    fn compare_plain_text() {
+        assert_eq!(compare_value(&text("b"), &text("a")), Option::Some(Ordering::Greater));
+        assert_eq!(compare_value(&text("10a"), &text("9a")), Option::Some(Ordering::Less));
+        assert_eq!(compare_value(&text("a"), &ColumnValue::Integer(1)), Option::None);
+        assert_eq!(compare_value(&ColumnValue::Null, &ColumnValue::Integer(1)), Option::None);
+    }
+}
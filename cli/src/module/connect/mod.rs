@@ -0,0 +1,688 @@
+mod sqlite_driver;
+mod file_driver;
+mod selector;
+mod transformer;
+#[cfg(feature = "postgres")]
+mod postgres_driver;
+#[cfg(feature = "mysql")]
+mod mysql_driver;
+
+use std::{error::Error, collections::HashMap};
+use crate::utils::error::ApplicationError;
+use selector::{Selector, value_to_string};
+use transformer::Transformer;
+use super::{config::{Connect, LocalConfig, ConnectParserTag, ConnectParserBook, ConnectParser}, connect_state::ConnectKey, download::{DownloadResult, DownloadTag, DownloadBook}};
+
+
+/// 记录对应的来源: (site, id, part)。
+pub type ConnectIdentity = (String, String, Option<i32>);
+
+/// 一条记录的处理结果。无法确定来源时identity为None。
+pub type ConnectResult = (Option<ConnectIdentity>, Result<DownloadResult, Box<dyn Error>>);
+
+pub struct ConnectModule<'t> {
+    config: &'t Option<Connect>
+}
+
+pub struct Connection<'t> {
+    config: &'t Connect,
+    driver: Box<dyn Driver>
+}
+
+pub struct Statement<'t> {
+    config: &'t Connect,
+    site_col_name: String,
+    pid_col_name: String,
+    key_col_name: Option<String>,
+    selectors: HashMap<String, Selector>,
+    transformers: HashMap<String, Transformer>,
+    rows: Box<dyn Rows + 't>,
+    current: Option<Row>,
+    done: bool
+}
+
+/// 选择器的检查统计。field为选择器在parser中的位置，例如title, tag.code, additional_info.md5。
+pub struct SelectorCheck {
+    pub site: String,
+    pub field: String,
+    pub selector: String,
+    pub success: usize,
+    pub failed: usize,
+    pub first_error: Option<String>
+}
+
+/// 数据源driver。driver负责按查询参数读取记录，并将记录转换为统一的Row，使列名识别、选择器等逻辑与数据源无关。
+/// 数据库driver通过bind_query将参数绑定进SQL；文件driver则直接按参数过滤记录。
+trait Driver {
+    /// limit是--limit给出的数量。数据库driver只通过query中的$limit参数限制数量，因此可以忽略它。
+    fn query<'s>(&'s mut self, query: &str, params: &HashMap<&str, QueryParam>, limit: Option<u32>) -> Result<Box<dyn Rows + 's>, Box<dyn Error>>;
+    /// 不同的split是否会读到不同的记录。
+    fn splittable(&self) -> bool {
+        true
+    }
+}
+
+/// 查询得到的记录流。
+trait Rows {
+    fn columns(&self) -> &[String];
+    fn next_row(&mut self) -> Option<Result<Row, Box<dyn Error>>>;
+    /// 读取出错后是否还能继续读取后续记录。
+    fn recoverable(&self) -> bool {
+        false
+    }
+}
+
+/// 一条查询记录，按列名读取。
+struct Row {
+    values: HashMap<String, ColumnValue>
+}
+
+/// 列的值。JSON等类型的列由driver转换为Text。
+#[derive(Clone, Debug, PartialEq)]
+enum ColumnValue {
+    Null,
+    Integer(i64),
+    Float(f64),
+    Text(String)
+}
+
+impl <'t> ConnectModule <'t> {
+    pub fn new(config: &'t LocalConfig) -> ConnectModule<'t> {
+        ConnectModule { config: &config.connect }
+    }
+    pub fn connect(&self) -> Result<Connection<'t>, Box<dyn Error>> {
+        let config = if let Some(c) = self.config { c }else{ return Result::Err(Box::new(ApplicationError::new("Config connect is not configured."))) };
+
+        let driver: Box<dyn Driver> = match config.driver.to_ascii_lowercase().as_str() {
+            "sqlite" => Box::new(sqlite_driver::SqliteDriver::connect(&config.url)?),
+            "jsonl" | "ndjson" => Box::new(file_driver::FileDriver::new(&config.url, file_driver::FileFormat::JsonLines)),
+            "csv" => Box::new(file_driver::FileDriver::new(&config.url, file_driver::FileFormat::Csv)),
+            #[cfg(feature = "postgres")]
+            "postgres" | "postgresql" => Box::new(postgres_driver::PostgresDriver::connect(&config.url)?),
+            #[cfg(feature = "mysql")]
+            "mysql" => Box::new(mysql_driver::MysqlDriver::connect(&config.url)?),
+            #[cfg(not(feature = "postgres"))]
+            "postgres" | "postgresql" => return Result::Err(Box::new(ApplicationError::new("Connect driver postgres is not enabled. Please build with feature 'postgres'."))),
+            #[cfg(not(feature = "mysql"))]
+            "mysql" => return Result::Err(Box::new(ApplicationError::new("Connect driver mysql is not enabled. Please build with feature 'mysql'."))),
+            _ => return Result::Err(Box::new(ApplicationError::new(&format!("Unsupported connect driver {}.", config.driver))))
+        };
+
+        Result::Ok(Connection::new(driver, config))
+    }
+}
+
+impl <'t> Connection <'t> {
+    fn new(driver: Box<dyn Driver>, config: &'t Connect) -> Connection<'t> {
+        Connection { driver, config }
+    }
+    pub fn splittable(&self) -> bool {
+        self.driver.splittable()
+    }
+    /// cursor作为$cursor参数传入查询，用于从某条记录的key之后继续读取。未指定时为NULL。
+    pub fn statement<'s>(&'s mut self, split: &[String], limit: Option<u32>, vars: &[(String, String)], cursor: Option<&ConnectKey>) -> Result<Statement<'s>, Box<dyn Error>> {
+        let mut params: HashMap<&str, QueryParam> = HashMap::new();
+        params.insert("split", QueryParam::List(split.to_vec()));
+        params.insert("site", QueryParam::List(self.config.parser.keys().cloned().collect()));
+        params.insert("limit", QueryParam::Integer(limit.unwrap_or(65536) as i64));
+        params.insert("cursor", QueryParam::Value(match cursor {
+            Some(ConnectKey::Integer(n)) => ColumnValue::Integer(*n),
+            Some(ConnectKey::Float(f)) => ColumnValue::Float(*f),
+            Some(ConnectKey::Text(t)) => ColumnValue::Text(t.clone()),
+            None => ColumnValue::Null
+        }));
+        for (k, v) in vars {
+            match params.get_mut(k.as_str()) {
+                Some(QueryParam::Text(t)) => {
+                    //同名变量指定多次时，作为列表参数
+                    let first = std::mem::take(t);
+                    params.insert(k, QueryParam::List(vec![first, v.clone()]));
+                },
+                Some(QueryParam::List(l)) if k != "split" && k != "site" => l.push(v.clone()),
+                Some(_) => return Result::Err(Box::new(ApplicationError::new(&format!("Variable ${} is reserved.", k)))),
+                None => { params.insert(k, QueryParam::Text(v.clone())); }
+            }
+        }
+        let rows = self.driver.query(&self.config.query, &params, limit)?;
+        let columns = rows.columns();
+        let site_col_name = if let Some(n) = find_column(columns, &SITE_COLUMNS) { n }
+            else { return Result::Err(Box::new(ApplicationError::new("Cannot find site column. Please specifiy a column named site, source or source_site."))) };
+        let pid_col_name = if let Some(n) = find_column(columns, &ID_COLUMNS) { n }
+            else { return Result::Err(Box::new(ApplicationError::new("Cannot find source id column. Please specifiy a column named id, pid or source_id."))) };
+        let key_col_name = find_column(columns, &["key"]);
+        let selectors = compile_selectors(&self.config.parser)?;
+        let mut transformers: HashMap<String, Transformer> = HashMap::new();
+        for (k, v) in &self.config.parser {
+            transformers.insert(k.clone(), Transformer::new(v)?);
+        }
+
+        Result::Ok(Statement::new(rows, self.config, site_col_name, pid_col_name, key_col_name, selectors, transformers))
+    }
+}
+
+impl <'t> Statement <'t> {
+    fn new(rows: Box<dyn Rows + 't>, config: &'t Connect, site_col_name: String, pid_col_name: String, key_col_name: Option<String>, selectors: HashMap<String, Selector>, transformers: HashMap<String, Transformer>) -> Statement<'t> {
+        Statement { config, rows, current: Option::None, done: false, site_col_name, pid_col_name, key_col_name, selectors, transformers }
+    }
+    /// 查询结果是否包含key列。
+    pub fn has_key(&self) -> bool {
+        self.key_col_name.is_some()
+    }
+    /// 读取当前记录的key。
+    pub fn key(&self) -> Option<ConnectKey> {
+        let key_col_name = self.key_col_name.as_ref()?;
+        match self.current.as_ref()?.values.get(key_col_name) {
+            Some(ColumnValue::Integer(n)) => Option::Some(ConnectKey::Integer(*n)),
+            Some(ColumnValue::Float(f)) => Option::Some(ConnectKey::Float(*f)),
+            Some(ColumnValue::Text(t)) => Option::Some(ConnectKey::Text(t.clone())),
+            _ => Option::None
+        }
+    }
+    pub fn next(&mut self) -> Option<ConnectResult> {
+        if self.done { return Option::None }
+        match self.rows.next_row() {
+            Some(Ok(row)) => {
+                let (identity, result) = self.process_row(&row);
+                self.current = Option::Some(row);
+                Option::Some((identity, result))
+            },
+            Some(Err(e)) => {
+                //读取出错时，报告这个错误。除非driver可以继续读取，否则结束读取
+                self.done = !self.rows.recoverable();
+                self.current = Option::None;
+                Option::Some((Option::None, Result::Err(e)))
+            },
+            None => Option::None
+        }
+    }
+    /// 读取下一行，逐个执行其parser中的选择器并将结果计入checks，而不生成结果。行本身无法处理时返回错误。
+    pub fn check_next(&mut self, checks: &mut Vec<SelectorCheck>) -> Option<Result<(), Box<dyn Error>>> {
+        if self.done { return Option::None }
+        let row = match self.rows.next_row()? {
+            Ok(row) => row,
+            Err(e) => {
+                self.done = !self.rows.recoverable();
+                return Option::Some(Result::Err(e))
+            }
+        };
+        let origin_site: String = match row.read_required(self.site_col_name.as_str()) {
+            Ok(r) => r,
+            Err(e) => return Option::Some(Result::Err(e))
+        };
+        let parser = if let Some(p) = self.config.parser.get(&origin_site) { p }else{
+            return Option::Some(Result::Err(Box::new(ApplicationError::new(&format!("Undefined site type '{}'.", origin_site)))))
+        };
+        if let Err(e) = row.read_required(self.pid_col_name.as_str()).and_then(|pid| parse_origin_pid(&pid)) {
+            return Option::Some(Result::Err(e))
+        }
+        check_row(&row, &origin_site, parser, &self.selectors, checks);
+        Option::Some(Result::Ok(()))
+    }
+    fn process_row(&self, row: &Row) -> ConnectResult {
+        let origin_site: String = match row.read_required(self.site_col_name.as_str()) {
+            Ok(r) => r,
+            Err(e) => return (Option::None, Result::Err(e))
+        };
+    
+        if let Some(parser) = self.config.parser.get(&origin_site) {
+            let origin_pid: String = match row.read_required(self.pid_col_name.as_str()) {
+                Ok(r) => r,
+                Err(e) => return (Option::None, Result::Err(e))
+            };
+            let (id, part) = match parse_origin_pid(&origin_pid) {
+                Ok(r) => r,
+                Err(e) => return (Option::None, Result::Err(e))
+            };
+            let download_result = match generate_download_result(row, parser, &self.selectors, &self.transformers[&origin_site]) {
+                Ok(r) => r,
+                Err(e) => return (Option::None, Result::Err(e))
+            };
+            (Option::Some((parser.site.clone(), id, part)), Result::Ok(download_result))
+        }else{
+            (Option::None, Result::Err(Box::new(ApplicationError::new(&format!("Undefined site type '{}'.", origin_site)))))
+        }
+    }
+}
+
+impl Row {
+    fn new(columns: &[String], values: Vec<ColumnValue>) -> Row {
+        Row { values: columns.iter().cloned().zip(values).collect() }
+    }
+    /// 以文本读取列。数字列会被转换为文本，NULL列返回None。
+    fn read(&self, column: &str) -> Result<Option<String>, Box<dyn Error>> {
+        match self.values.get(column) {
+            Some(ColumnValue::Null) => Result::Ok(Option::None),
+            Some(ColumnValue::Integer(n)) => Result::Ok(Option::Some(n.to_string())),
+            Some(ColumnValue::Float(f)) => Result::Ok(Option::Some(f.to_string())),
+            Some(ColumnValue::Text(t)) => Result::Ok(Option::Some(t.clone())),
+            None => Result::Err(Box::new(ApplicationError::new(&format!("Column '{}' is not exist.", column))))
+        }
+    }
+    fn read_required(&self, column: &str) -> Result<String, Box<dyn Error>> {
+        self.read(column)?.ok_or_else(|| Box::new(ApplicationError::new(&format!("Column '{}' is NULL.", column))) as Box<dyn Error>)
+    }
+}
+
+const SITE_COLUMNS: [&str; 3] = ["site", "source", "source_site"];
+const ID_COLUMNS: [&str; 3] = ["id", "pid", "source_id"];
+
+fn find_column(columns: &[String], names: &[&str]) -> Option<String> {
+    names.iter().find(|n| columns.iter().any(|c| c == *n)).map(|n| n.to_string())
+}
+
+/// 查询参数。列表参数展开为`(?, ?, ...)`，其他参数展开为`?`，值通过绑定传入，而不是拼接进SQL。占位符的具体形式由driver决定。
+enum QueryParam {
+    Text(String),
+    Integer(i64),
+    List(Vec<String>),
+    Value(ColumnValue)
+}
+
+/// 将SQL中的`$name`变量替换为占位符，并按顺序生成绑定值。
+/// 单引号字符串、双引号标识符、`--`行注释与`/* */`块注释中的内容不做替换。
+fn bind_query<F: Fn(usize) -> String>(sql: &str, params: &HashMap<&str, QueryParam>, placeholder: F) -> Result<(String, Vec<ColumnValue>), Box<dyn Error>> {
+    if sql.trim().is_empty() {
+        return Result::Err(Box::new(ApplicationError::new("Config connect.query is not configured.")))
+    }
+    let mut ret = String::with_capacity(sql.len());
+    let mut values: Vec<ColumnValue> = Vec::new();
+    let mut quote: Option<char> = Option::None;
+    let mut line_comment = false;
+    let mut block_comment = false;
+    let mut chars = sql.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let next = chars.peek().map(|(_, n)| *n);
+        if line_comment {
+            if c == '\n' { line_comment = false }
+            ret.push(c);
+        }else if block_comment {
+            ret.push(c);
+            if c == '*' && next == Option::Some('/') {
+                ret.push('/');
+                chars.next();
+                block_comment = false;
+            }
+        }else if let Some(q) = quote {
+            //引号内的两个连续引号是转义，按两次切换处理即可
+            if c == q { quote = Option::None }
+            ret.push(c);
+        }else if c == '\'' || c == '"' {
+            quote = Option::Some(c);
+            ret.push(c);
+        }else if c == '-' && next == Option::Some('-') {
+            line_comment = true;
+            ret.push(c);
+        }else if c == '/' && next == Option::Some('*') {
+            ret.push_str("/*");
+            chars.next();
+            block_comment = true;
+        }else if c == '$' {
+            let mut end = i + 1;
+            while let Some(&(j, n)) = chars.peek() {
+                if n.is_ascii_alphanumeric() || n == '_' {
+                    end = j + n.len_utf8();
+                    chars.next();
+                }else{
+                    break
+                }
+            }
+            let name = &sql[i + 1..end];
+            if name.is_empty() {
+                ret.push(c);
+                continue
+            }
+            match params.get(name) {
+                Some(QueryParam::Text(t)) => {
+                    ret.push_str(&placeholder(values.len()));
+                    values.push(ColumnValue::Text(t.clone()));
+                },
+                Some(QueryParam::Integer(n)) => {
+                    ret.push_str(&placeholder(values.len()));
+                    values.push(ColumnValue::Integer(*n));
+                },
+                Some(QueryParam::Value(v)) => {
+                    ret.push_str(&placeholder(values.len()));
+                    values.push(v.clone());
+                },
+                Some(QueryParam::List(l)) => {
+                    let placeholders: Vec<String> = (0..l.len()).map(|k| placeholder(values.len() + k)).collect();
+                    ret.push('(');
+                    ret.push_str(&placeholders.join(", "));
+                    ret.push(')');
+                    values.extend(l.iter().map(|t| ColumnValue::Text(t.clone())));
+                },
+                None => return Result::Err(Box::new(ApplicationError::new(&format!("Variable ${} is not defined. Please specify it by --var {}=<value>.", name, name))))
+            }
+        }else{
+            ret.push(c);
+        }
+    }
+    Result::Ok((ret, values))
+}
+
+fn generate_download_result(row: &Row, parser: &ConnectParser, selectors: &HashMap<String, Selector>, transformer: &Transformer) -> Result<DownloadResult, Box<dyn Error>> {
+    let title = if let Some(ref addr) = parser.title { Option::Some(stat_read_str(row, selectors, addr)?) }else{ Option::None };
+    let description = if let Some(ref addr) = parser.description { Option::Some(stat_read_str(row, selectors, addr)?) }else{ Option::None };
+    let tags = if let Some(ref t) = parser.tag { Option::Some(stat_read_tag(row, selectors, t)?) }else{ Option::None };
+    let books = if let Some(ref b) = parser.book { Option::Some(stat_read_book(row, selectors, b)?) }else{ Option::None };
+    let relations = if let Some(ref r) = parser.relation { Option::Some(stat_read_relations(row, selectors, &r.selector)?) }else{ Option::None };
+    let additional_info = if let Some(ref a) = parser.additional_info { Option::Some(stat_read_additional_info(row, selectors, a)?) }else{ Option::None };
+    
+    let result = DownloadResult { title, description, tags, books, relations, additional_info };
+    Result::Ok(transformer.apply(result))
+}
+
+fn check_row(row: &Row, site: &str, parser: &ConnectParser, selectors: &HashMap<String, Selector>, checks: &mut Vec<SelectorCheck>) {
+    let mut record = |field: &str, selector: &str, result: Result<(), Box<dyn Error>>| {
+        let i = if let Some(i) = checks.iter().position(|c| c.site == site && c.field == field) { i }else{
+            checks.push(SelectorCheck { site: site.to_string(), field: field.to_string(), selector: selector.to_string(), success: 0, failed: 0, first_error: Option::None });
+            checks.len() - 1
+        };
+        let check = &mut checks[i];
+        match result {
+            Ok(_) => check.success += 1,
+            Err(e) => {
+                check.failed += 1;
+                if check.first_error.is_none() { check.first_error = Option::Some(e.to_string()) }
+            }
+        }
+    };
+    if let Some(ref s) = parser.title { record("title", s, stat_read_str(row, selectors, s).map(|_| ())) }
+    if let Some(ref s) = parser.description { record("description", s, stat_read_str(row, selectors, s).map(|_| ())) }
+    if let Some(ref t) = parser.tag {
+        match select_items(row, selectors, &t.selector) {
+            Ok(items) => {
+                record("tag.selector", &t.selector, Result::Ok(()));
+                for item in items.iter() {
+                    record("tag.code", &t.code, field_selector(item, selectors, &t.code, "Tag.code").map(|_| ()));
+                    for (field, s, name) in [("tag.name", &t.name, "Tag.name"), ("tag.other_name", &t.other_name, "Tag.other_name"), ("tag.type", &t.tag_type, "Tag.type")] {
+                        if let Some(s) = s { record(field, s, field_selector_option(item, selectors, s, name).map(|_| ())) }
+                    }
+                }
+            },
+            Err(e) => record("tag.selector", &t.selector, Result::Err(e))
+        }
+    }
+    if let Some(ref b) = parser.book {
+        match select_items(row, selectors, &b.selector) {
+            Ok(items) => {
+                record("book.selector", &b.selector, Result::Ok(()));
+                for item in items.iter() {
+                    record("book.code", &b.code, field_selector(item, selectors, &b.code, "Book.code").map(|_| ()));
+                    for (field, s, name) in [("book.title", &b.title, "Book.title"), ("book.other_title", &b.other_title, "Book.other_title")] {
+                        if let Some(s) = s { record(field, s, field_selector_option(item, selectors, s, name).map(|_| ())) }
+                    }
+                }
+            },
+            Err(e) => record("book.selector", &b.selector, Result::Err(e))
+        }
+    }
+    if let Some(ref r) = parser.relation {
+        for (i, s) in r.selector.iter().enumerate() {
+            record(&format!("relation.selector[{}]", i), s, stat_read_relations(row, selectors, &vec![s.clone()]).map(|_| ()));
+        }
+    }
+    if let Some(ref a) = parser.additional_info {
+        for (field, s) in a.iter() {
+            let single: HashMap<String, String> = HashMap::from([(field.clone(), s.clone())]);
+            record(&format!("additional_info.{}", field), s, stat_read_additional_info(row, selectors, &single).map(|_| ()));
+        }
+    }
+}
+
+fn parse_origin_pid(pid: &str) -> Result<(String, Option<i32>), Box<dyn Error>> {
+    if let Some(idx) = pid.find(|c| c == '_') {
+        let id: String = pid[..idx].to_string();
+        let part: i32 = pid[idx + 1..].trim_matches('p').parse()?;
+        Result::Ok((id, Option::Some(part)))
+    }else{
+        let id: String = pid.to_string();
+        Result::Ok((id, Option::None))
+    }
+}
+
+fn stat_read_str(row: &Row, selectors: &HashMap<String, Selector>, selector: &str) -> Result<String, Box<dyn Error>> {
+    if let Some(value) = selectors[selector].select_row(row, true)? {
+        //null column也视作不是字符串
+        if let Some(s) = value_to_string(&value) {
+            Result::Ok(s)
+        }else{
+            Result::Err(Box::new(ApplicationError::new(&format!("Selector '{}' is not string.", selector))))
+        }
+    }else{
+        Result::Err(Box::new(ApplicationError::new(&format!("Selector '{}' is not exist.", selector))))
+    }
+}
+
+fn stat_read_tag(row: &Row, selectors: &HashMap<String, Selector>, tag: &ConnectParserTag) -> Result<Vec<DownloadTag>, Box<dyn Error>> {
+    let mut ret: Vec<DownloadTag> = Vec::new();
+    for item in select_items(row, selectors, &tag.selector)? {
+        let code = field_selector(&item, selectors, &tag.code, "Tag.code")?;
+        let name = if let Some(ref n) = tag.name { field_selector_option(&item, selectors, n, "Tag.name")? }else{ Option::None };
+        let other_name = if let Some(ref n) = tag.other_name { field_selector_option(&item, selectors, n, "Tag.other_name")? }else{ Option::None };
+        let tag_type = if let Some(ref n) = tag.tag_type { field_selector_option(&item, selectors, n, "Tag.type")? }else{ Option::None };
+        if code.is_empty() {
+            if (name.is_some() && !name.unwrap().is_empty()) || (other_name.is_some() && !other_name.unwrap().is_empty()) {
+                return Result::Err(Box::new(ApplicationError::new(&format!("Tag code is empty but name/other_name not. (name/other_name={})", tag.name.as_ref().unwrap_or_else(|| tag.other_name.as_ref().unwrap()))))) 
+            }
+        }else{
+            ret.push(DownloadTag { code, name, other_name, tag_type })
+        }
+    }
+    Result::Ok(ret)
+}
+
+fn stat_read_book(row: &Row, selectors: &HashMap<String, Selector>, book: &ConnectParserBook) -> Result<Vec<DownloadBook>, Box<dyn Error>> {
+    let mut ret: Vec<DownloadBook> = Vec::new();
+    for item in select_items(row, selectors, &book.selector)? {
+        let code = field_selector(&item, selectors, &book.code, "Book.code")?;
+        let title = if let Some(ref n) = book.title { field_selector_option(&item, selectors, n, "Book.title")? }else{ Option::None };
+        let other_title = if let Some(ref n) = book.other_title { field_selector_option(&item, selectors, n, "Book.other_title")? }else{ Option::None };
+        ret.push(DownloadBook { code, title, other_title })
+    }
+    Result::Ok(ret)
+}
+
+/// 选择tag/book所需的数组。
+fn select_items(row: &Row, selectors: &HashMap<String, Selector>, selector: &str) -> Result<Vec<serde_json::Value>, Box<dyn Error>> {
+    match selectors[selector].select_row(row, false)? {
+        Some(serde_json::Value::Array(arr)) => Result::Ok(arr),
+        //skip null column
+        Some(serde_json::Value::Null) => Result::Ok(Vec::new()),
+        Some(_) => Result::Err(Box::new(ApplicationError::new(&format!("Selector '{}' is not an array.", selector)))),
+        None => Result::Err(Box::new(ApplicationError::new(&format!("Selector '{}' is not exist.", selector))))
+    }
+}
+
+fn stat_read_relations(row: &Row, selectors: &HashMap<String, Selector>, relation_selectors: &Vec<String>) -> Result<Vec<i64>, Box<dyn Error>> {
+    let mut ret: Vec<i64> = Vec::new();
+    for selector in relation_selectors {
+        if let Some(json) = selectors[selector].select_row(row, false)? {
+            if let Some(arr) = json.as_array() {
+                for item in arr {
+                    if let Some(i) = item.as_i64() {
+                        ret.push(i);
+                    }else if let Some(s) = item.as_str() {
+                        ret.push(s.parse()?);
+                    }else{
+                        return Result::Err(Box::new(ApplicationError::new(&format!("Selector '{}'s item is not number or string.", selector))))   
+                    }
+                }
+            }else if let Some(i) = json.as_i64() {
+                ret.push(i);
+            }else if let Some(s) = json.as_str() {
+                ret.push(s.parse()?);
+            }else if let Some(_) = json.as_null() {
+                //skip
+            }else{
+                return Result::Err(Box::new(ApplicationError::new(&format!("Selector '{}' is not array, number or string.", selector))))       
+            }
+        }else{
+            return Result::Err(Box::new(ApplicationError::new(&format!("Selector '{}' is not exist.", selector))))   
+        }
+    }
+    Result::Ok(ret)
+}
+
+fn stat_read_additional_info(row: &Row, selectors: &HashMap<String, Selector>, info_selectors: &HashMap<String, String>) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let mut ret: HashMap<String, String> = HashMap::new();
+    for (field, selector) in info_selectors {
+        if let Some(json) = selectors[selector].select_row(row, false)? {
+            if let Some(s) = value_to_string(&json) {
+                ret.insert(field.clone(), s);
+            }else if let Some(_) = json.as_null() {
+                //skip
+            }else{
+                return Result::Err(Box::new(ApplicationError::new(&format!("Selector '{}' is not string.", selector))))       
+            }
+        }else{
+            return Result::Err(Box::new(ApplicationError::new(&format!("Selector '{}' is not exist.", selector))))   
+        }
+    }
+
+    Result::Ok(ret)
+}
+
+fn field_selector(json: &serde_json::Value, selectors: &HashMap<String, Selector>, field: &str, field_name: &str) -> Result<String, Box<dyn Error>> {
+    if let Some(s) = field_selector_option(json, selectors, field, field_name)? {
+        Result::Ok(s)
+    }else{
+        return Result::Err(Box::new(ApplicationError::new(&format!("{} '{}' returns null.", field_name, field))))
+    }
+}
+
+fn field_selector_option(json: &serde_json::Value, selectors: &HashMap<String, Selector>, field: &str, field_name: &str) -> Result<Option<String>, Box<dyn Error>> {
+    if let Some(value) = selectors[field].select_value(json)? {
+        if let Some(s) = value_to_string(&value) { 
+            Result::Ok(Option::Some(s))
+        }else if value.is_null() {
+            Result::Ok(Option::None)
+        }else{
+            return Result::Err(Box::new(ApplicationError::new(&format!("{} '{}' is not string.", field_name, field))))       
+        }
+    }else{
+        Result::Ok(Option::None)
+    }
+}
+
+/// 预先解析parser中的全部选择器，以选择器文本为key。语法错误在开始读取之前就报告。
+fn compile_selectors(parsers: &HashMap<String, ConnectParser>) -> Result<HashMap<String, Selector>, Box<dyn Error>> {
+    let mut ret: HashMap<String, Selector> = HashMap::new();
+    for parser in parsers.values() {
+        let mut texts: Vec<&String> = Vec::new();
+        texts.extend(parser.title.iter());
+        texts.extend(parser.description.iter());
+        if let Some(ref t) = parser.tag {
+            texts.push(&t.selector);
+            texts.push(&t.code);
+            texts.extend([&t.name, &t.other_name, &t.tag_type].into_iter().flatten());
+        }
+        if let Some(ref b) = parser.book {
+            texts.push(&b.selector);
+            texts.push(&b.code);
+            texts.extend([&b.title, &b.other_title].into_iter().flatten());
+        }
+        if let Some(ref r) = parser.relation {
+            texts.extend(r.selector.iter());
+        }
+        if let Some(ref a) = parser.additional_info {
+            texts.extend(a.values());
+        }
+        for text in texts {
+            if !ret.contains_key(text) {
+                ret.insert(text.clone(), Selector::parse(text)?);
+            }
+        }
+    }
+    Result::Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params() -> HashMap<&'static str, QueryParam> {
+        HashMap::from([
+            ("site", QueryParam::Text("pixiv".to_string())),
+            ("since", QueryParam::Integer(100)),
+            ("key", QueryParam::Value(ColumnValue::Float(1.5))),
+            ("ids", QueryParam::List(vec!["1".to_string(), "2".to_string()]))
+        ])
+    }
+
+    #[test]
+    fn bind_numbered_placeholders() {
+        let (sql, values) = bind_query("SELECT * FROM t WHERE site = $site AND id IN $ids AND k > $key AND t > $since", &params(), |i| format!("${}", i + 1)).unwrap();
+        assert_eq!(sql, "SELECT * FROM t WHERE site = $1 AND id IN ($2, $3) AND k > $4 AND t > $5");
+        assert_eq!(values, vec![
+            ColumnValue::Text("pixiv".to_string()), ColumnValue::Text("1".to_string()), ColumnValue::Text("2".to_string()),
+            ColumnValue::Float(1.5), ColumnValue::Integer(100)
+        ]);
+    }
+
+    #[test]
+    fn bind_question_placeholders() {
+        let (sql, values) = bind_query("SELECT * FROM t WHERE site = $site AND id IN $ids", &params(), |_| "?".to_string()).unwrap();
+        assert_eq!(sql, "SELECT * FROM t WHERE site = ? AND id IN (?, ?)");
+        assert_eq!(values.len(), 3);
+    }
+
+    #[test]
+    fn bind_skips_quoted_and_bare_dollar() {
+        let (sql, values) = bind_query("SELECT '$site', $ FROM t WHERE a = $site", &params(), |_| "?".to_string()).unwrap();
+        assert_eq!(sql, "SELECT '$site', $ FROM t WHERE a = ?");
+        assert_eq!(values, vec![ColumnValue::Text("pixiv".to_string())]);
+    }
+
+    #[test]
+    fn bind_skips_comments_and_identifiers() {
+        let sql = "SELECT \"$site\", 'it''s $site' -- $since\nFROM t /* $ids */ WHERE a = $site /*/ $key */ AND b = $since";
+        let (ret, values) = bind_query(sql, &params(), |_| "?".to_string()).unwrap();
+        assert_eq!(ret, "SELECT \"$site\", 'it''s $site' -- $since\nFROM t /* $ids */ WHERE a = ? /*/ $key */ AND b = ?");
+        assert_eq!(values, vec![ColumnValue::Text("pixiv".to_string()), ColumnValue::Integer(100)]);
+        //注释中未定义的变量不会报错
+        assert!(bind_query("SELECT 1 -- $undefined", &params(), |_| "?".to_string()).is_ok());
+        assert!(bind_query("SELECT 1 - $since", &params(), |_| "?".to_string()).is_ok());
+    }
+
+    #[test]
+    fn bind_errors() {
+        assert!(bind_query("  ", &params(), |_| "?".to_string()).is_err());
+        assert!(bind_query("SELECT $undefined", &params(), |_| "?".to_string()).is_err());
+    }
+
+    #[test]
+    fn file_driver_reads_beyond_default_limit() {
+        let path = std::env::temp_dir().join(format!("hedge-cli-test-limit-{}.jsonl", std::process::id()));
+        let lines: Vec<String> = (1..=70000).map(|i| format!(r#"{{"site":"s","id":"{}","title":"t{}"}}"#, i, i)).collect();
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        let parser: ConnectParser = toml::from_str(r#"site = "site""#).unwrap();
+        let config = Connect { driver: "jsonl".to_string(), url: path.to_string_lossy().to_string(), query: String::new(), parser: HashMap::from([("s".to_string(), parser)]) };
+        let count = |limit: Option<u32>| {
+            let mut conn = Connection::new(Box::new(file_driver::FileDriver::new(&config.url, file_driver::FileFormat::JsonLines)), &config);
+            let mut stat = conn.statement(&["all".to_string()], limit, &[], Option::None).unwrap();
+            let mut count = 0;
+            while let Some((_, result)) = stat.next() {
+                result.unwrap();
+                count += 1;
+            }
+            count
+        };
+        let (unlimited, limited) = (count(Option::None), count(Option::Some(100)));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(unlimited, 70000);
+        assert_eq!(limited, 100);
+    }
+
+    #[test]
+    fn read_row_values() {
+        let columns = vec!["a".to_string(), "b".to_string(), "c".to_string(), "d".to_string()];
+        let row = Row::new(&columns, vec![ColumnValue::Null, ColumnValue::Integer(3), ColumnValue::Float(0.5), ColumnValue::Text("x".to_string())]);
+        assert_eq!(row.read("a").unwrap(), Option::None);
+        assert_eq!(row.read("b").unwrap(), Option::Some("3".to_string()));
+        assert_eq!(row.read("c").unwrap(), Option::Some("0.5".to_string()));
+        assert_eq!(row.read("d").unwrap(), Option::Some("x".to_string()));
+        assert!(row.read("e").is_err());
+        assert!(row.read_required("a").is_err());
+    }
+}
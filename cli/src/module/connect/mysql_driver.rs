@@ -0,0 +1,93 @@
+use std::{error::Error, collections::HashMap};
+use mysql::{Conn, Opts, Params, Value, prelude::Queryable};
+use super::{Driver, Rows, Row, ColumnValue, QueryParam, bind_query};
+
+
+pub struct MysqlDriver {
+    conn: Conn
+}
+
+struct MysqlRows<'t> {
+    result: mysql::QueryResult<'t, 't, 't, mysql::Binary>,
+    columns: Vec<String>
+}
+
+impl MysqlDriver {
+    pub fn connect(url: &str) -> Result<MysqlDriver, Box<dyn Error>> {
+        Result::Ok(MysqlDriver { conn: Conn::new(Opts::from_url(url)?)? })
+    }
+}
+
+impl Driver for MysqlDriver {
+    fn query<'s>(&'s mut self, query: &str, params: &HashMap<&str, QueryParam>, _limit: Option<u32>) -> Result<Box<dyn Rows + 's>, Box<dyn Error>> {
+        let (sql, values) = bind_query(query, params, |_| "?".to_string())?;
+        let stat = self.conn.prep(sql)?;
+        let columns = stat.columns().iter().map(|c| c.name_str().to_string()).collect();
+        let params: Vec<Value> = values.iter().map(to_param).collect();
+        let params = if params.is_empty() { Params::Empty }else{ Params::Positional(params) };
+        let result = self.conn.exec_iter(stat, params)?;
+        Result::Ok(Box::new(MysqlRows { result, columns }))
+    }
+}
+
+impl Rows for MysqlRows<'_> {
+    fn columns(&self) -> &[String] {
+        &self.columns
+    }
+    fn next_row(&mut self) -> Option<Result<Row, Box<dyn Error>>> {
+        let row = match self.result.next()? {
+            Ok(row) => row,
+            Err(e) => return Option::Some(Result::Err(Box::new(e)))
+        };
+        let values = row.unwrap().into_iter().map(read_column).collect();
+        Option::Some(Result::Ok(Row::new(&self.columns, values)))
+    }
+}
+
+fn to_param(value: &ColumnValue) -> Value {
+    match value {
+        ColumnValue::Null => Value::NULL,
+        ColumnValue::Integer(n) => Value::Int(*n),
+        ColumnValue::Float(f) => Value::Double(*f),
+        ColumnValue::Text(t) => Value::Bytes(t.as_bytes().to_vec())
+    }
+}
+
+fn read_column(value: Value) -> ColumnValue {
+    match value {
+        Value::NULL => ColumnValue::Null,
+        Value::Int(n) => ColumnValue::Integer(n),
+        Value::UInt(n) => ColumnValue::Integer(n as i64),
+        Value::Float(f) => ColumnValue::Float(f as f64),
+        Value::Double(f) => ColumnValue::Float(f),
+        //JSON、文本等类型的列以Bytes返回
+        Value::Bytes(b) => ColumnValue::Text(String::from_utf8_lossy(&b).into_owned()),
+        //日期、时间类型的列转换为文本
+        v => ColumnValue::Text(v.as_sql(false).trim_matches('\'').to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_params() {
+        assert_eq!(to_param(&ColumnValue::Null), Value::NULL);
+        assert_eq!(to_param(&ColumnValue::Integer(-3)), Value::Int(-3));
+        assert_eq!(to_param(&ColumnValue::Float(1.5)), Value::Double(1.5));
+        assert_eq!(to_param(&ColumnValue::Text("文本".to_string())), Value::Bytes("文本".as_bytes().to_vec()));
+    }
+
+    #[test]
+    fn convert_columns() {
+        assert_eq!(read_column(Value::NULL), ColumnValue::Null);
+        assert_eq!(read_column(Value::Int(-3)), ColumnValue::Integer(-3));
+        assert_eq!(read_column(Value::UInt(7)), ColumnValue::Integer(7));
+        assert_eq!(read_column(Value::Float(0.5)), ColumnValue::Float(0.5));
+        assert_eq!(read_column(Value::Double(2.25)), ColumnValue::Float(2.25));
+        assert_eq!(read_column(Value::Bytes(br#"{"a":1}"#.to_vec())), ColumnValue::Text(r#"{"a":1}"#.to_string()));
+        assert_eq!(read_column(Value::Date(2024, 1, 2, 3, 4, 5, 0)), ColumnValue::Text("2024-01-02 03:04:05".to_string()));
+        assert_eq!(read_column(Value::Time(false, 0, 1, 2, 3, 0)), ColumnValue::Text("001:02:03".to_string()));
+    }
+}
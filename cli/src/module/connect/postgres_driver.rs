@@ -0,0 +1,129 @@
+use std::{error::Error, collections::HashMap};
+use postgres::{Client, NoTls, RowIter, fallible_iterator::FallibleIterator, types::{ToSql, Type}};
+use tokio::task::block_in_place;
+use crate::utils::error::ApplicationError;
+use super::{Driver, Rows, Row, ColumnValue, QueryParam, bind_query};
+
+
+/// postgres的同步Client内部使用了自己的tokio runtime，在CLI的异步上下文中调用时需要通过block_in_place。
+pub struct PostgresDriver {
+    client: Option<Client>
+}
+
+struct PostgresRows<'t> {
+    iter: RowIter<'t>,
+    columns: Vec<String>,
+    types: Vec<Type>
+}
+
+impl PostgresDriver {
+    pub fn connect(url: &str) -> Result<PostgresDriver, Box<dyn Error>> {
+        let client = block_in_place(|| Client::connect(url, NoTls))?;
+        Result::Ok(PostgresDriver { client: Option::Some(client) })
+    }
+}
+
+impl Drop for PostgresDriver {
+    fn drop(&mut self) {
+        if let Some(client) = self.client.take() {
+            block_in_place(|| drop(client))
+        }
+    }
+}
+
+impl Driver for PostgresDriver {
+    fn query<'s>(&'s mut self, query: &str, params: &HashMap<&str, QueryParam>, _limit: Option<u32>) -> Result<Box<dyn Rows + 's>, Box<dyn Error>> {
+        let (sql, values) = bind_query(query, params, |i| format!("${}", i + 1))?;
+        let client = self.client.as_mut().unwrap();
+        let stat = block_in_place(|| client.prepare(&sql))?;
+        let columns = stat.columns().iter().map(|c| c.name().to_string()).collect();
+        let types = stat.columns().iter().map(|c| c.type_().clone()).collect();
+        let params = values.iter().zip(stat.params()).map(|(v, ty)| to_param(v, ty)).collect::<Result<Vec<_>, _>>()?;
+        let iter = block_in_place(move || client.query_raw(&stat, params.iter().map(|p| p.as_ref())))?;
+        Result::Ok(Box::new(PostgresRows { iter, columns, types }))
+    }
+}
+
+impl Rows for PostgresRows<'_> {
+    fn columns(&self) -> &[String] {
+        &self.columns
+    }
+    fn next_row(&mut self) -> Option<Result<Row, Box<dyn Error>>> {
+        let row = match block_in_place(|| self.iter.next()) {
+            Ok(Some(row)) => row,
+            Ok(None) => return Option::None,
+            Err(e) => return Option::Some(Result::Err(Box::new(e)))
+        };
+        let mut values = Vec::with_capacity(self.columns.len());
+        for (i, ty) in self.types.iter().enumerate() {
+            match read_column(&row, i, ty) {
+                Ok(v) => values.push(v),
+                Err(e) => return Option::Some(Result::Err(e))
+            }
+        }
+        Option::Some(Result::Ok(Row::new(&self.columns, values)))
+    }
+}
+
+/// 按参数在语句中推断出的类型转换绑定值。
+fn to_param(value: &ColumnValue, ty: &Type) -> Result<Box<dyn ToSql + Sync>, Box<dyn Error>> {
+    let text = match value {
+        ColumnValue::Null => Option::None,
+        ColumnValue::Integer(n) => Option::Some(n.to_string()),
+        ColumnValue::Float(f) => Option::Some(f.to_string()),
+        ColumnValue::Text(t) => Option::Some(t.clone())
+    };
+    Result::Ok(match *ty {
+        Type::INT2 => Box::new(text.map(|s| s.parse::<i16>()).transpose()?),
+        Type::INT4 => Box::new(text.map(|s| s.parse::<i32>()).transpose()?),
+        Type::INT8 => Box::new(text.map(|s| s.parse::<i64>()).transpose()?),
+        Type::FLOAT4 => Box::new(text.map(|s| s.parse::<f32>()).transpose()?),
+        Type::FLOAT8 => Box::new(text.map(|s| s.parse::<f64>()).transpose()?),
+        Type::JSON | Type::JSONB => Box::new(text.map(|s| serde_json::from_str::<serde_json::Value>(&s)).transpose()?),
+        _ => Box::new(text)
+    })
+}
+
+fn read_column(row: &postgres::Row, i: usize, ty: &Type) -> Result<ColumnValue, Box<dyn Error>> {
+    let value = match *ty {
+        Type::BOOL => row.try_get::<_, Option<bool>>(i)?.map(|b| ColumnValue::Integer(b as i64)),
+        Type::INT2 => row.try_get::<_, Option<i16>>(i)?.map(|n| ColumnValue::Integer(n as i64)),
+        Type::INT4 => row.try_get::<_, Option<i32>>(i)?.map(|n| ColumnValue::Integer(n as i64)),
+        Type::INT8 => row.try_get::<_, Option<i64>>(i)?.map(ColumnValue::Integer),
+        Type::FLOAT4 => row.try_get::<_, Option<f32>>(i)?.map(|f| ColumnValue::Float(f as f64)),
+        Type::FLOAT8 => row.try_get::<_, Option<f64>>(i)?.map(ColumnValue::Float),
+        Type::JSON | Type::JSONB => row.try_get::<_, Option<serde_json::Value>>(i)?.map(|v| ColumnValue::Text(v.to_string())),
+        Type::TEXT | Type::VARCHAR | Type::BPCHAR | Type::NAME | Type::UNKNOWN => row.try_get::<_, Option<String>>(i)?.map(ColumnValue::Text),
+        _ => return Result::Err(Box::new(ApplicationError::new(&format!("Unsupported type {} of column '{}'. Please cast it to text in query.", ty, row.columns()[i].name()))))
+    };
+    Result::Ok(value.unwrap_or(ColumnValue::Null))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn param(value: ColumnValue, ty: &Type) -> String {
+        format!("{:?}", to_param(&value, ty).unwrap())
+    }
+
+    #[test]
+    fn convert_params_by_type() {
+        assert_eq!(param(ColumnValue::Text("12".to_string()), &Type::INT2), "Some(12)");
+        assert_eq!(param(ColumnValue::Integer(12), &Type::INT4), "Some(12)");
+        assert_eq!(param(ColumnValue::Integer(12), &Type::INT8), "Some(12)");
+        assert_eq!(param(ColumnValue::Integer(2), &Type::FLOAT8), "Some(2.0)");
+        assert_eq!(param(ColumnValue::Float(0.5), &Type::FLOAT4), "Some(0.5)");
+        assert_eq!(param(ColumnValue::Integer(12), &Type::TEXT), "Some(\"12\")");
+        assert_eq!(param(ColumnValue::Text(r#"{"a":1}"#.to_string()), &Type::JSONB), "Some(Object {\"a\": Number(1)})");
+        assert_eq!(param(ColumnValue::Null, &Type::INT8), "None");
+        assert_eq!(param(ColumnValue::Null, &Type::TEXT), "None");
+    }
+
+    #[test]
+    fn convert_invalid_params() {
+        assert!(to_param(&ColumnValue::Text("abc".to_string()), &Type::INT8).is_err());
+        assert!(to_param(&ColumnValue::Integer(100000), &Type::INT2).is_err());
+        assert!(to_param(&ColumnValue::Text("{".to_string()), &Type::JSON).is_err());
+    }
+}
@@ -0,0 +1,417 @@
+use std::error::Error;
+use regex::Regex;
+use serde_json::Value;
+use crate::utils::error::ApplicationError;
+use super::Row;
+
+
+/// 选择器。语法为`路径|路径|'默认值' ~ 转换 ~ 转换`:
+/// * 路径以`.`分隔各级字段，`$`表示值本身，`[n]`按下标选择数组元素(负数从末尾计)，`*`或`[*]`选择数组的所有元素或对象的所有值，
+///   `[key=value]`选择数组中key字段等于value的元素，`["a.b"]`选择名称中含有特殊字符的字段。使用了`*`或过滤后，结果为数组；
+/// * 以`|`分隔多个备选路径，依次尝试，取第一个非空的结果。以引号包围的备选项是字面量，可作为默认值；
+/// * 以`~`追加转换，依次作用于结果: lower, upper, trim, regex(pattern), replace(pattern, replacement), split(sep), join(sep)。
+pub struct Selector {
+    text: String,
+    alternatives: Vec<Alternative>,
+    transforms: Vec<Transform>
+}
+
+enum Alternative {
+    Path(Vec<Segment>),
+    Literal(String)
+}
+
+#[derive(Debug, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(i64),
+    Wildcard,
+    Filter(String, String)
+}
+
+enum Transform {
+    Lower,
+    Upper,
+    Trim,
+    /// 提取第一个捕获组，没有捕获组时提取整个匹配。不匹配时结果为空。
+    Regex(Regex),
+    Replace(Regex, String),
+    Split(String),
+    Join(String)
+}
+
+impl Selector {
+    pub fn parse(text: &str) -> Result<Selector, Box<dyn Error>> {
+        let err = |msg: &str| Box::new(ApplicationError::new(&format!("Selector '{}' syntax error: {}", text, msg)));
+        let mut parts = split_top_level(text, '~').into_iter();
+        let alternatives = split_top_level(parts.next().unwrap_or_default(), '|').into_iter().map(|a| {
+            let a = a.trim();
+            if a.starts_with('\'') || a.starts_with('"') {
+                unquote(a).map(Alternative::Literal)
+            }else{
+                parse_path(a).map(Alternative::Path)
+            }
+        }).collect::<Result<Vec<_>, String>>().map_err(|e| err(&e))?;
+        let transforms = parts.map(|t| parse_transform(t.trim())).collect::<Result<Vec<_>, String>>().map_err(|e| err(&e))?;
+        Result::Ok(Selector { text: text.to_string(), alternatives, transforms })
+    }
+    /// 从记录中选择。路径的第一段是列名，后续各段在列内容解析得到的JSON中选择。
+    /// 路径只有列名时，raw为true则直接以列的文本作为结果，否则尝试将其解析为JSON。列为NULL时结果为null。
+    pub fn select_row(&self, row: &Row, raw: bool) -> Result<Option<Value>, Box<dyn Error>> {
+        self.select(|segments| {
+            let Some(Segment::Key(column)) = segments.first() else {
+                return Result::Err(Box::new(ApplicationError::new(&format!("Selector '{}' must start with a column name.", self.text))) as Box<dyn Error>)
+            };
+            let text = if let Some(text) = row.read(column)? { text }else{ return Result::Ok(Option::Some(Value::Null)) };
+            if segments.len() == 1 {
+                if raw {
+                    Result::Ok(Option::Some(Value::String(text)))
+                }else{
+                    Result::Ok(Option::Some(serde_json::from_str(&text).unwrap_or(Value::String(text))))
+                }
+            }else{
+                let json: Value = serde_json::from_str(&text)?;
+                select_path(&json, &segments[1..]).map_err(|e| Box::new(ApplicationError::new(&format!("Selector '{}': {}", self.text, e))) as Box<dyn Error>)
+            }
+        })
+    }
+    /// 从JSON值中选择。路径的第一段就是JSON中的字段。
+    pub fn select_value(&self, json: &Value) -> Result<Option<Value>, Box<dyn Error>> {
+        self.select(|segments| {
+            //只有一个字段名的路径作用于非对象的值时结果为空，例如元素是字符串的数组中，各项都没有name字段
+            if let ([Segment::Key(_)], false) = (segments, json.is_object()) {
+                return Result::Ok(Option::None)
+            }
+            select_path(json, segments).map_err(|e| Box::new(ApplicationError::new(&format!("Selector '{}': {}", self.text, e))) as Box<dyn Error>)
+        })
+    }
+    fn select<F: Fn(&[Segment]) -> Result<Option<Value>, Box<dyn Error>>>(&self, select: F) -> Result<Option<Value>, Box<dyn Error>> {
+        let mut result = Option::None;
+        for alternative in self.alternatives.iter() {
+            result = match alternative {
+                Alternative::Path(segments) => select(segments)?,
+                Alternative::Literal(s) => Option::Some(Value::String(s.clone()))
+            };
+            if is_present(&result) { break }
+        }
+        match result {
+            Some(mut value) => {
+                for transform in self.transforms.iter() {
+                    value = transform.apply(value);
+                }
+                Result::Ok(Option::Some(value))
+            },
+            None => Result::Ok(Option::None)
+        }
+    }
+}
+
+/// 将字符串与数字作为文本读取。
+pub fn value_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Option::Some(s.clone()),
+        Value::Number(n) => Option::Some(n.to_string()),
+        _ => Option::None
+    }
+}
+
+fn is_present(value: &Option<Value>) -> bool {
+    match value {
+        None | Some(Value::Null) => false,
+        Some(Value::Array(a)) => !a.is_empty(),
+        Some(_) => true
+    }
+}
+
+fn select_path(json: &Value, segments: &[Segment]) -> Result<Option<Value>, String> {
+    let mut current: Vec<&Value> = vec![json];
+    let mut multi = false;
+    for segment in segments {
+        let mut next = Vec::new();
+        for value in current {
+            match (segment, value) {
+                (_, Value::Null) => {},
+                (Segment::Key(k), Value::Object(m)) => if let Some(v) = m.get(k) { next.push(v) },
+                (Segment::Index(i), Value::Array(a)) => {
+                    let i = if *i < 0 { a.len() as i64 + *i }else{ *i };
+                    if let Some(v) = usize::try_from(i).ok().and_then(|i| a.get(i)) { next.push(v) }
+                },
+                (Segment::Wildcard, Value::Array(a)) => next.extend(a.iter()),
+                (Segment::Wildcard, Value::Object(m)) => next.extend(m.values()),
+                (Segment::Filter(k, expected), Value::Array(a)) => next.extend(a.iter().filter(|v| v.get(k).and_then(value_to_string).map(|s| &s == expected).unwrap_or(false))),
+                //展开后的多个值中，结构不符合的值被忽略
+                _ if multi => {},
+                (Segment::Key(k), _) => return Result::Err(format!("'{}' is not an object.", k)),
+                (Segment::Index(i), _) => return Result::Err(format!("'[{}]' is not an array.", i)),
+                (Segment::Wildcard, _) => return Result::Err("'*' is not an array or object.".to_string()),
+                (Segment::Filter(k, v), _) => return Result::Err(format!("'[{}={}]' is not an array.", k, v))
+            }
+        }
+        if matches!(segment, Segment::Wildcard | Segment::Filter(_, _)) { multi = true }
+        current = next;
+    }
+    if multi {
+        Result::Ok(Option::Some(Value::Array(current.into_iter().cloned().collect())))
+    }else{
+        Result::Ok(current.first().map(|v| (*v).clone()))
+    }
+}
+
+impl Transform {
+    fn apply(&self, value: Value) -> Value {
+        match self {
+            Transform::Split(sep) => match value {
+                Value::Array(a) => Value::Array(a.into_iter().flat_map(|v| split_value(v, sep)).collect()),
+                v => Value::Array(split_value(v, sep))
+            },
+            Transform::Join(sep) => match value {
+                Value::Array(a) => Value::String(a.iter().filter_map(value_to_string).collect::<Vec<_>>().join(sep)),
+                v => v
+            },
+            _ => match value {
+                Value::Array(a) => Value::Array(a.into_iter().map(|v| self.apply_one(v)).filter(|v| !v.is_null()).collect()),
+                v => self.apply_one(v)
+            }
+        }
+    }
+    fn apply_one(&self, value: Value) -> Value {
+        let s = if let Some(s) = value_to_string(&value) { s }else{ return value };
+        match self {
+            Transform::Lower => Value::String(s.to_lowercase()),
+            Transform::Upper => Value::String(s.to_uppercase()),
+            Transform::Trim => Value::String(s.trim().to_string()),
+            Transform::Regex(r) => match r.captures(&s) {
+                Some(c) => Value::String(c.get(1).or_else(|| c.get(0)).map(|m| m.as_str().to_string()).unwrap_or_default()),
+                None => Value::Null
+            },
+            Transform::Replace(r, replacement) => Value::String(r.replace_all(&s, replacement.as_str()).into_owned()),
+            Transform::Split(_) | Transform::Join(_) => value
+        }
+    }
+}
+
+fn split_value(value: Value, sep: &str) -> Vec<Value> {
+    match value_to_string(&value) {
+        Some(s) => s.split(sep).map(|p| p.trim()).filter(|p| !p.is_empty()).map(|p| Value::String(p.to_string())).collect(),
+        None => if value.is_null() { Vec::new() }else{ vec![value] }
+    }
+}
+
+fn parse_path(text: &str) -> Result<Vec<Segment>, String> {
+    if text.is_empty() { return Result::Err("empty path.".to_string()) }
+    //`$`表示值本身，用于元素就是字符串的数组
+    if text == "$" { return Result::Ok(Vec::new()) }
+    let mut segments = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut key = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => {
+                if !key.is_empty() { segments.push(key_segment(std::mem::take(&mut key))) }
+            },
+            '[' => {
+                if !key.is_empty() { segments.push(key_segment(std::mem::take(&mut key))) }
+                let mut content = String::new();
+                let mut quote: Option<char> = Option::None;
+                loop {
+                    match chars.next() {
+                        None => return Result::Err("'[' is not closed.".to_string()),
+                        Some(']') if quote.is_none() => break,
+                        Some('\\') if quote.is_some() => {
+                            content.push('\\');
+                            if let Some(n) = chars.next() { content.push(n) }
+                        },
+                        Some(q) if q == '\'' || q == '"' => {
+                            if quote == Option::Some(q) { quote = Option::None }else if quote.is_none() { quote = Option::Some(q) }
+                            content.push(q);
+                        },
+                        Some(n) => content.push(n)
+                    }
+                }
+                segments.push(bracket_segment(content.trim())?);
+            },
+            c => key.push(c)
+        }
+    }
+    if !key.is_empty() { segments.push(key_segment(key)) }
+    Result::Ok(segments)
+}
+
+fn key_segment(key: String) -> Segment {
+    if key == "*" { Segment::Wildcard }else{ Segment::Key(key) }
+}
+
+fn bracket_segment(content: &str) -> Result<Segment, String> {
+    if content == "*" {
+        Result::Ok(Segment::Wildcard)
+    }else if content.starts_with('\'') || content.starts_with('"') {
+        unquote(content).map(Segment::Key)
+    }else if let Ok(i) = content.parse::<i64>() {
+        Result::Ok(Segment::Index(i))
+    }else if let Some((k, v)) = content.split_once('=') {
+        let v = v.trim();
+        let v = if v.starts_with('\'') || v.starts_with('"') { unquote(v)? }else{ v.to_string() };
+        Result::Ok(Segment::Filter(k.trim().to_string(), v))
+    }else{
+        Result::Err(format!("'[{}]' is not an index, '*' or filter.", content))
+    }
+}
+
+fn parse_transform(text: &str) -> Result<Transform, String> {
+    let (name, args) = match text.find('(') {
+        Some(i) => {
+            if !text.ends_with(')') { return Result::Err(format!("'(' of transform '{}' is not closed.", text)) }
+            let args = split_top_level(&text[i + 1..text.len() - 1], ',').into_iter().map(|a| {
+                let a = a.trim();
+                if a.starts_with('\'') || a.starts_with('"') { unquote(a) }else{ Result::Ok(a.to_string()) }
+            }).collect::<Result<Vec<_>, String>>()?;
+            (text[..i].trim(), args)
+        },
+        None => (text, Vec::new())
+    };
+    let regex = |p: &str| Regex::new(p).map_err(|e| format!("regex '{}' error: {}", p, e));
+    match (name, args.as_slice()) {
+        ("lower", []) => Result::Ok(Transform::Lower),
+        ("upper", []) => Result::Ok(Transform::Upper),
+        ("trim", []) => Result::Ok(Transform::Trim),
+        ("regex", [p]) => Result::Ok(Transform::Regex(regex(p)?)),
+        ("replace", [p, r]) => Result::Ok(Transform::Replace(regex(p)?, r.clone())),
+        ("split", [sep]) => Result::Ok(Transform::Split(sep.clone())),
+        ("join", [sep]) => Result::Ok(Transform::Join(sep.clone())),
+        ("lower" | "upper" | "trim" | "regex" | "replace" | "split" | "join", _) => Result::Err(format!("wrong number of arguments for transform '{}'.", name)),
+        _ => Result::Err(format!("unknown transform '{}'.", name))
+    }
+}
+
+/// 按分隔符切分，忽略引号、括号内的分隔符。
+fn split_top_level(text: &str, sep: char) -> Vec<&str> {
+    let mut ret = Vec::new();
+    let mut quote: Option<char> = Option::None;
+    let mut depth = 0;
+    let mut start = 0;
+    let mut escape = false;
+    for (i, c) in text.char_indices() {
+        if escape {
+            escape = false;
+        }else if let Some(q) = quote {
+            if c == '\\' { escape = true }else if c == q { quote = Option::None }
+        }else if c == '\'' || c == '"' {
+            quote = Option::Some(c);
+        }else if c == '[' || c == '(' {
+            depth += 1;
+        }else if c == ']' || c == ')' {
+            depth -= 1;
+        }else if c == sep && depth == 0 {
+            ret.push(&text[start..i]);
+            start = i + c.len_utf8();
+        }
+    }
+    ret.push(&text[start..]);
+    ret
+}
+
+/// 去除首尾的引号。引号内`\\`和`\`加引号表示转义，其他的`\`原样保留，以便书写正则表达式。
+fn unquote(text: &str) -> Result<String, String> {
+    let q = text.chars().next().unwrap_or_default();
+    if text.len() < 2 || !text.ends_with(q) {
+        return Result::Err(format!("quote of {} is not closed.", text))
+    }
+    let mut ret = String::new();
+    let mut chars = text[1..text.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some(n) if n == '\\' || n == q => ret.push(n),
+                Some(n) => { ret.push('\\'); ret.push(n) },
+                None => ret.push('\\')
+            }
+        }else{
+            ret.push(c);
+        }
+    }
+    Result::Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::*;
+
+    fn key(k: &str) -> Segment {
+        Segment::Key(k.to_string())
+    }
+
+    fn select(text: &str, json: &Value) -> Option<Value> {
+        Selector::parse(text).unwrap().select_value(json).unwrap()
+    }
+
+    #[test]
+    fn parse_path_segments() {
+        assert_eq!(parse_path("a.b").unwrap(), vec![key("a"), key("b")]);
+        assert_eq!(parse_path("$").unwrap(), vec![]);
+        assert_eq!(parse_path("a[0][-1]").unwrap(), vec![key("a"), Segment::Index(0), Segment::Index(-1)]);
+        assert_eq!(parse_path("a.*.b[*]").unwrap(), vec![key("a"), Segment::Wildcard, key("b"), Segment::Wildcard]);
+        assert_eq!(parse_path("tags[type=1]").unwrap(), vec![key("tags"), Segment::Filter("type".to_string(), "1".to_string())]);
+        assert_eq!(parse_path("tags[name='a]b']").unwrap(), vec![key("tags"), Segment::Filter("name".to_string(), "a]b".to_string())]);
+        assert_eq!(parse_path(r#"a["b.c"]"#).unwrap(), vec![key("a"), key("b.c")]);
+    }
+
+    #[test]
+    fn parse_path_errors() {
+        assert!(parse_path("").is_err());
+        assert!(parse_path("a[0").is_err());
+        assert!(parse_path("a[b]").is_err());
+    }
+
+    #[test]
+    fn split_top_level_ignores_quotes_and_brackets() {
+        assert_eq!(split_top_level("a|b|'c'", '|'), vec!["a", "b", "'c'"]);
+        assert_eq!(split_top_level("a['x|y']|b", '|'), vec!["a['x|y']", "b"]);
+        assert_eq!(split_top_level("a ~ replace('~', '-') ~ lower", '~'), vec!["a ", " replace('~', '-') ", " lower"]);
+        assert_eq!(split_top_level(r"'a\'|b'|c", '|'), vec![r"'a\'|b'", "c"]);
+        assert_eq!(split_top_level("", '|'), vec![""]);
+    }
+
+    #[test]
+    fn unquote_escapes() {
+        assert_eq!(unquote("'abc'").unwrap(), "abc");
+        assert_eq!(unquote(r#""a\"b""#).unwrap(), r#"a"b"#);
+        assert_eq!(unquote(r"'a\\b'").unwrap(), r"a\b");
+        assert_eq!(unquote(r"'\d+'").unwrap(), r"\d+");
+        assert!(unquote("'abc").is_err());
+        assert!(unquote("'").is_err());
+    }
+
+    #[test]
+    fn select_path_values() {
+        let json = json!({"a": {"b": [1, 2, 3]}, "tags": [{"name": "x", "type": 1}, {"name": "y", "type": 2}, "z"]});
+        assert_eq!(select_path(&json, &parse_path("a.b[-1]").unwrap()).unwrap(), Option::Some(json!(3)));
+        assert_eq!(select_path(&json, &parse_path("a.c").unwrap()).unwrap(), Option::None);
+        assert_eq!(select_path(&json, &parse_path("tags[*].name").unwrap()).unwrap(), Option::Some(json!(["x", "y"])));
+        assert_eq!(select_path(&json, &parse_path("tags[type=2].name").unwrap()).unwrap(), Option::Some(json!(["y"])));
+        assert_eq!(select_path(&json, &parse_path("a.*").unwrap()).unwrap(), Option::Some(json!([[1, 2, 3]])));
+        assert!(select_path(&json, &parse_path("a.b.c").unwrap()).is_err());
+        assert!(select_path(&json, &parse_path("a[0]").unwrap()).is_err());
+    }
+
+    #[test]
+    fn selector_alternatives_and_transforms() {
+        let json = json!({"title": null, "name": " Hello_World ", "tags": "a, b,,c", "empty": []});
+        assert_eq!(select("title|name ~ trim ~ lower", &json), Option::Some(json!("hello_world")));
+        assert_eq!(select("empty|'none'", &json), Option::Some(json!("none")));
+        assert_eq!(select("missing|'default'", &json), Option::Some(json!("default")));
+        assert_eq!(select("missing", &json), Option::None);
+        assert_eq!(select("tags ~ split(',') ~ upper", &json), Option::Some(json!(["A", "B", "C"])));
+        assert_eq!(select("name ~ regex('_(\\w+)')", &json), Option::Some(json!("World")));
+        assert_eq!(select("name ~ replace('_', ' ') ~ trim", &json), Option::Some(json!("Hello World")));
+        assert!(Selector::parse("name ~ unknown").is_err());
+        assert!(Selector::parse("name ~ split").is_err());
+    }
+
+    #[test]
+    fn single_key_on_non_object() {
+        assert_eq!(select("name", &json!("tag")), Option::None);
+        assert_eq!(select("$", &json!("tag")), Option::Some(json!("tag")));
+        assert!(Selector::parse("a.b").unwrap().select_value(&json!("tag")).is_err());
+    }
+}
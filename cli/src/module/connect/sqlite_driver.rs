@@ -0,0 +1,59 @@
+use std::{error::Error, collections::HashMap};
+use super::{Driver, Rows, Row, ColumnValue, QueryParam, bind_query};
+
+
+pub struct SqliteDriver {
+    conn: sqlite::Connection
+}
+
+struct SqliteRows<'t> {
+    stat: sqlite::Statement<'t>,
+    columns: Vec<String>
+}
+
+impl SqliteDriver {
+    pub fn connect(url: &str) -> Result<SqliteDriver, Box<dyn Error>> {
+        Result::Ok(SqliteDriver { conn: sqlite::open(url)? })
+    }
+}
+
+impl Driver for SqliteDriver {
+    fn query<'s>(&'s mut self, query: &str, params: &HashMap<&str, QueryParam>, _limit: Option<u32>) -> Result<Box<dyn Rows + 's>, Box<dyn Error>> {
+        let (sql, values) = bind_query(query, params, |_| "?".to_string())?;
+        let mut stat = self.conn.prepare(sql)?;
+        let values: Vec<sqlite::Value> = values.iter().map(|v| match v {
+            ColumnValue::Null => sqlite::Value::Null,
+            ColumnValue::Integer(n) => sqlite::Value::Integer(*n),
+            ColumnValue::Float(f) => sqlite::Value::Float(*f),
+            ColumnValue::Text(t) => sqlite::Value::String(t.clone())
+        }).collect();
+        stat.bind(&values[..])?;
+        let columns = stat.column_names().to_vec();
+        Result::Ok(Box::new(SqliteRows { stat, columns }))
+    }
+}
+
+impl Rows for SqliteRows<'_> {
+    fn columns(&self) -> &[String] {
+        &self.columns
+    }
+    fn next_row(&mut self) -> Option<Result<Row, Box<dyn Error>>> {
+        match self.stat.next() {
+            Ok(sqlite::State::Row) => {},
+            Ok(sqlite::State::Done) => return Option::None,
+            Err(e) => return Option::Some(Result::Err(Box::new(e)))
+        }
+        let mut values = Vec::with_capacity(self.columns.len());
+        for i in 0..self.columns.len() {
+            values.push(match self.stat.read::<sqlite::Value, usize>(i) {
+                Ok(sqlite::Value::Null) => ColumnValue::Null,
+                Ok(sqlite::Value::Integer(n)) => ColumnValue::Integer(n),
+                Ok(sqlite::Value::Float(f)) => ColumnValue::Float(f),
+                Ok(sqlite::Value::String(t)) => ColumnValue::Text(t),
+                Ok(sqlite::Value::Binary(b)) => ColumnValue::Text(String::from_utf8_lossy(&b).into_owned()),
+                Err(e) => return Option::Some(Result::Err(Box::new(e)))
+            });
+        }
+        Option::Some(Result::Ok(Row::new(&self.columns, values)))
+    }
+}
@@ -0,0 +1,260 @@
+use std::collections::{HashMap, HashSet};
+use regex::Regex;
+use crate::{module::{config::ConnectParser, download::DownloadResult}, utils::error::ApplicationError};
+
+
+/// 转换器。在选择器得到结果之后，按parser配置依次对指定字段实施转换，最后按blocklist过滤tag。
+/// 转换的顺序为: translate_underscore_to_space, rewrite, tag_type_map。
+pub struct Transformer {
+    steps: Vec<TransformStep>,
+    tag_blocklist: Vec<Regex>,
+    tag_type_blocklist: HashSet<String>
+}
+
+struct TransformStep {
+    fields: TransformFields,
+    op: TransformOp
+}
+
+enum TransformOp {
+    UnderscoreToSpace,
+    Rewrite(Regex, String),
+    Map(HashMap<String, String>)
+}
+
+/// 转换作用的字段。
+struct TransformFields {
+    title: bool,
+    description: bool,
+    tag_code: bool,
+    tag_name: bool,
+    tag_other_name: bool,
+    tag_type: bool,
+    book_code: bool,
+    book_title: bool,
+    book_other_title: bool,
+    additional_info: HashSet<String>
+}
+
+impl Transformer {
+    pub fn new(parser: &ConnectParser) -> Result<Transformer, ApplicationError> {
+        let mut steps = Vec::new();
+        if let Some(ref fields) = parser.translate_underscore_to_space {
+            if !fields.is_empty() {
+                steps.push(TransformStep { fields: TransformFields::new(fields)?, op: TransformOp::UnderscoreToSpace });
+            }
+        }
+        if let Some(ref rules) = parser.rewrite {
+            for rule in rules {
+                let regex = Regex::new(&rule.pattern).map_err(|e| ApplicationError::new(&format!("Rewrite pattern '{}' error: {}", rule.pattern, e)))?;
+                let fields = if let Some(ref f) = rule.fields { TransformFields::new(f)? }else{ TransformFields::new(&vec!["tag.code".to_string()])? };
+                steps.push(TransformStep { fields, op: TransformOp::Rewrite(regex, rule.replacement.clone()) });
+            }
+        }
+        if let Some(ref map) = parser.tag_type_map {
+            steps.push(TransformStep { fields: TransformFields::new(&vec!["tag.type".to_string()])?, op: TransformOp::Map(map.clone()) });
+        }
+        let tag_blocklist = if let Some(ref list) = parser.tag_blocklist {
+            //按完整匹配处理，避免普通的tag名称意外匹配到其他tag
+            list.iter().map(|p| Regex::new(&format!("^(?:{})$", p)).map_err(|e| ApplicationError::new(&format!("Tag blocklist pattern '{}' error: {}", p, e)))).collect::<Result<Vec<_>, _>>()?
+        }else{
+            Vec::new()
+        };
+        let tag_type_blocklist = parser.tag_type_blocklist.as_ref().map(|l| l.iter().cloned().collect()).unwrap_or_default();
+
+        Result::Ok(Transformer { steps, tag_blocklist, tag_type_blocklist })
+    }
+    pub fn apply(&self, mut r: DownloadResult) -> DownloadResult {
+        for step in self.steps.iter() {
+            r = step.apply(r);
+        }
+        if let Some(ref mut tags) = r.tags {
+            let mut exists: HashSet<(String, Option<String>)> = HashSet::new();
+            tags.retain(|tag| {
+                if self.tag_blocklist.iter().any(|p| p.is_match(&tag.code)) { return false }
+                if let Some(ref t) = tag.tag_type {
+                    if self.tag_type_blocklist.contains(t) { return false }
+                }
+                //改写后可能出现重复的tag
+                exists.insert((tag.code.clone(), tag.tag_type.clone()))
+            });
+        }
+        r
+    }
+}
+
+impl TransformStep {
+    fn apply(&self, mut r: DownloadResult) -> DownloadResult {
+        let fields = &self.fields;
+        if fields.title { r.title = r.title.map(|t| self.op.apply(&t)) }
+        if fields.description { r.description = r.description.map(|t| self.op.apply(&t)) }
+        if fields.tag_code || fields.tag_name || fields.tag_other_name || fields.tag_type {
+            if let Some(ref mut tags) = r.tags {
+                for tag in tags.iter_mut() {
+                    if fields.tag_code { tag.code = self.op.apply(&tag.code) }
+                    if fields.tag_name { tag.name = tag.name.as_ref().map(|t| self.op.apply(t)) }
+                    if fields.tag_other_name { tag.other_name = tag.other_name.as_ref().map(|t| self.op.apply(t)) }
+                    if fields.tag_type { tag.tag_type = tag.tag_type.as_ref().map(|t| self.op.apply(t)) }
+                }
+            }
+        }
+        if fields.book_code || fields.book_title || fields.book_other_title {
+            if let Some(ref mut books) = r.books {
+                for book in books.iter_mut() {
+                    if fields.book_code { book.code = self.op.apply(&book.code) }
+                    if fields.book_title { book.title = book.title.as_ref().map(|t| self.op.apply(t)) }
+                    if fields.book_other_title { book.other_title = book.other_title.as_ref().map(|t| self.op.apply(t)) }
+                }
+            }
+        }
+        if !fields.additional_info.is_empty() {
+            if let Some(ref mut info) = r.additional_info {
+                for (k, v) in info.iter_mut() {
+                    if fields.additional_info.contains(k) {
+                        *v = self.op.apply(v)
+                    }
+                }
+            }
+        }
+        r
+    }
+}
+
+impl TransformOp {
+    fn apply(&self, origin: &str) -> String {
+        match self {
+            TransformOp::UnderscoreToSpace => origin.replace('_', " "),
+            TransformOp::Rewrite(regex, replacement) => regex.replace_all(origin, replacement.as_str()).into_owned(),
+            //未在映射表中的值保持原样
+            TransformOp::Map(map) => map.get(origin).cloned().unwrap_or_else(|| origin.to_string())
+        }
+    }
+}
+
+impl TransformFields {
+    fn empty() -> TransformFields {
+        TransformFields {
+            title: false,
+            description: false,
+            tag_code: false,
+            tag_name: false,
+            tag_other_name: false,
+            tag_type: false,
+            book_code: false,
+            book_title: false,
+            book_other_title: false,
+            additional_info: HashSet::new()
+        }
+    }
+    fn new(types: &Vec<String>) -> Result<TransformFields, ApplicationError> {
+        let mut ret = Self::empty();
+
+        for str in types {
+            match str.as_str() {
+                "title" => ret.title = true,
+                "description" => ret.description = true,
+                "tag.code" => ret.tag_code = true,
+                "tag.name" => ret.tag_name = true,
+                "tag.other_name" => ret.tag_other_name = true,
+                "tag.type" => ret.tag_type = true,
+                "book.code" => ret.book_code = true,
+                "book.title" => ret.book_title = true,
+                "book.other_title" => ret.book_other_title = true,
+                _ => if let Some(field) = str.strip_prefix("additional_info.") {
+                    ret.additional_info.insert(field.to_string());
+                }else{
+                    return Result::Err(ApplicationError::new(&format!("Unrecoginzed transform field {}.", str)))
+                }
+            }
+        }
+
+        Result::Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use crate::module::download::{DownloadBook, DownloadTag};
+    use super::*;
+
+    fn transformer(parser: &str) -> Transformer {
+        let parser: ConnectParser = toml::from_str(&format!("site = \"site\"\n{}", parser)).unwrap();
+        Transformer::new(&parser).unwrap()
+    }
+
+    fn tag(code: &str, tag_type: &str) -> DownloadTag {
+        DownloadTag { code: code.to_string(), name: Option::Some(code.to_string()), other_name: Option::None, tag_type: Option::Some(tag_type.to_string()) }
+    }
+
+    fn result(tags: Vec<DownloadTag>) -> DownloadResult {
+        DownloadResult {
+            title: Option::Some("a_title".to_string()),
+            description: Option::Some("a_description".to_string()),
+            tags: Option::Some(tags),
+            books: Option::Some(vec![DownloadBook { code: "book_1".to_string(), title: Option::Some("book_title".to_string()), other_title: Option::None }]),
+            relations: Option::None,
+            additional_info: Option::Some(HashMap::from([("md5".to_string(), "a_b".to_string()), ("url".to_string(), "a_b".to_string())]))
+        }
+    }
+
+    fn tags(r: &DownloadResult) -> Vec<(&str, Option<&str>)> {
+        r.tags.as_ref().unwrap().iter().map(|t| (t.code.as_str(), t.tag_type.as_deref())).collect()
+    }
+
+    #[test]
+    fn underscore_to_space_on_selected_fields() {
+        let t = transformer(r#"translate_underscore_to_space = ["title", "tag.code", "book.title", "additional_info.md5"]"#);
+        let r = t.apply(result(vec![tag("long_hair", "general")]));
+        assert_eq!(r.title.as_deref(), Option::Some("a title"));
+        assert_eq!(r.description.as_deref(), Option::Some("a_description"));
+        assert_eq!(tags(&r), vec![("long hair", Option::Some("general"))]);
+        assert_eq!(r.tags.as_ref().unwrap()[0].name.as_deref(), Option::Some("long_hair"));
+        let book = &r.books.as_ref().unwrap()[0];
+        assert_eq!((book.code.as_str(), book.title.as_deref()), ("book_1", Option::Some("book title")));
+        let info = r.additional_info.as_ref().unwrap();
+        assert_eq!((info["md5"].as_str(), info["url"].as_str()), ("a b", "a_b"));
+    }
+
+    #[test]
+    fn rewrite_then_map_types() {
+        let t = transformer(r#"
+            translate_underscore_to_space = ["tag.code"]
+            rewrite = [
+                { pattern = " ?\\(cosplay\\)$", replacement = "" },
+                { pattern = "^a (.+)$", replacement = "$1", fields = ["title"] }
+            ]
+            tag_type_map = { "1" = "artist", "0" = "general" }
+        "#);
+        let r = t.apply(result(vec![tag("alice_(cosplay)", "0"), tag("bob", "1"), tag("carol", "9")]));
+        assert_eq!(tags(&r), vec![("alice", Option::Some("general")), ("bob", Option::Some("artist")), ("carol", Option::Some("9"))]);
+        assert_eq!(r.title.as_deref(), Option::Some("a_title"));
+
+        //rewrite在underscore转换之后执行，因此可以匹配转换后的空格
+        let r = t.apply(DownloadResult { title: Option::Some("a b".to_string()), ..result(vec![]) });
+        assert_eq!(r.title.as_deref(), Option::Some("b"));
+    }
+
+    #[test]
+    fn blocklist_and_dedupe() {
+        let t = transformer(r#"
+            rewrite = [{ pattern = " \\(.+\\)$", replacement = "" }]
+            tag_blocklist = ["tagme", "bad.*"]
+            tag_type_blocklist = ["meta"]
+        "#);
+        let r = t.apply(result(vec![
+            tag("alice (cosplay)", "character"), tag("alice", "character"), tag("alice", "artist"),
+            tag("tagme", "general"), tag("tagme2", "general"), tag("badge", "general"), tag("highres", "meta")
+        ]));
+        //blocklist是完整匹配；改写后重复的tag只保留第一个，类型不同的不算重复
+        assert_eq!(tags(&r), vec![("alice", Option::Some("character")), ("alice", Option::Some("artist")), ("tagme2", Option::Some("general"))]);
+    }
+
+    #[test]
+    fn invalid_config() {
+        for parser in [r#"translate_underscore_to_space = ["tag.unknown"]"#, r#"rewrite = [{ pattern = "(", replacement = "" }]"#, r#"tag_blocklist = ["["]"#] {
+            let parser: ConnectParser = toml::from_str(&format!("site = \"site\"\n{}", parser)).unwrap();
+            assert!(Transformer::new(&parser).is_err());
+        }
+    }
+}
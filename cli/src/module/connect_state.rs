@@ -0,0 +1,99 @@
+use std::{collections::HashMap, fs, io::ErrorKind, path::PathBuf};
+use serde::{Serialize, Deserialize};
+use crate::module::config::LocalConfig;
+
+/// 在本地记录每个split的connect进度，用于--resume和--since。
+/// 进度按频道分别存储，并以数据源(driver、url与query)区分，切换数据源后不会沿用之前的进度。
+pub struct ConnectState {
+    path: PathBuf,
+    source: String,
+    data: HashMap<String, HashMap<String, ConnectSplitState>>
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct ConnectSplitState {
+    /// 最后一条已处理的记录的key。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<ConnectKey>,
+    /// 最后一次成功完成的运行处理到的记录的key。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since: Option<ConnectKey>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_success_time: Option<String>
+}
+
+/// 记录的key，保留其在数据库中的类型，以便绑定回查询时按原类型比较。
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum ConnectKey {
+    Integer(i64),
+    Float(f64),
+    Text(String)
+}
+
+impl ConnectState {
+    pub fn new(config: &LocalConfig, channel: &str) -> ConnectState {
+        let path = config.work_path.userdata_path.join("cli/connect").join(format!("{}.state.json", channel));
+        //url中可能包含密码，因此只记录数据源的摘要
+        let source = config.connect.as_ref().map(|c| format!("{:016x}", fnv1a(&[c.driver.as_str(), c.url.as_str(), c.query.as_str()]))).unwrap_or_default();
+        let data = match fs::read_to_string(&path) {
+            Err(e) => if e.kind() == ErrorKind::NotFound {
+                HashMap::new()
+            }else{
+                panic!("Cannot load connect state {}: {}", path.to_str().unwrap(), e)
+            },
+            Ok(t) => match serde_json::from_str(&t) {
+                Err(e) => panic!("Connect state format error: {}", e),
+                Ok(t) => t
+            }
+        };
+        ConnectState { path, source, data }
+    }
+    pub fn get(&self, split: &str) -> Option<&ConnectSplitState> {
+        self.data.get(&self.source).and_then(|d| d.get(split))
+    }
+    pub fn set_cursor(&mut self, split: &str, key: ConnectKey) {
+        self.split_mut(split).cursor = Option::Some(key);
+        self.save();
+    }
+    /// 一次运行成功完成。将since推进到本次处理到的位置。
+    pub fn complete(&mut self, split: &str) {
+        let state = self.split_mut(split);
+        if state.cursor.is_some() {
+            state.since = state.cursor.clone();
+        }
+        state.last_success_time = Option::Some(chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string());
+        self.save();
+    }
+    fn split_mut(&mut self, split: &str) -> &mut ConnectSplitState {
+        self.data.entry(self.source.clone()).or_default().entry(split.to_string()).or_default()
+    }
+    fn save(&self) {
+        let s = match serde_json::to_string_pretty(&self.data) {
+            Err(e) => panic!("Connect state format error: {}", e),
+            Ok(s) => s
+        };
+        if let Some(parent) = self.path.parent() {
+            if let Err(e) = fs::create_dir_all(parent) {
+                panic!("Cannot create connect state dir {}: {}", parent.to_str().unwrap(), e)
+            }
+        }
+        if let Err(e) = fs::write(&self.path, s) {
+            panic!("Cannot write connect state {}: {}", self.path.to_str().unwrap(), e)
+        }
+    }
+}
+
+/// FNV-1a摘要。标准库的Hasher不保证在不同版本间结果一致，不能用于持久化的key。
+fn fnv1a(parts: &[&str]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for (i, part) in parts.iter().enumerate() {
+        //以0分隔各部分，避免不同的拆分方式得到相同的摘要
+        let separator: &[u8] = if i > 0 { &[0] }else{ &[] };
+        for b in separator.iter().chain(part.as_bytes()) {
+            hash ^= *b as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    hash
+}
@@ -0,0 +1,60 @@
+use std::{cell::RefCell, collections::HashMap, fs, io::ErrorKind, path::PathBuf};
+use crate::module::config::LocalConfig;
+
+/// 在本地记录每个来源数据的下载失败次数，用于--max-attempts策略。记录按channel分开存放。
+pub struct DownloadAttempts {
+    path: PathBuf,
+    data: RefCell<HashMap<String, HashMap<String, u32>>>
+}
+
+impl DownloadAttempts {
+    pub fn new(config: &LocalConfig, channel: &str) -> DownloadAttempts {
+        let path = config.work_path.userdata_path.join("cli/download").join(format!("{}.attempts.json", channel));
+        let data = match fs::read_to_string(&path) {
+            Err(e) => if e.kind() == ErrorKind::NotFound {
+                HashMap::new()
+            }else{
+                panic!("Cannot load download attempts {}: {}", path.to_str().unwrap(), e)
+            },
+            Ok(t) => match serde_json::from_str(&t) {
+                Err(e) => panic!("Download attempts format error: {}", e),
+                Ok(t) => t
+            }
+        };
+        DownloadAttempts { path, data: RefCell::new(data) }
+    }
+    pub fn get(&self, site: &str, source_id: &str) -> u32 {
+        self.data.borrow().get(site).and_then(|f| f.get(source_id)).copied().unwrap_or(0)
+    }
+    /// 失败次数+1，并返回新的失败次数。
+    pub fn increase(&self, site: &str, source_id: &str) -> u32 {
+        let cnt = {
+            let mut data = self.data.borrow_mut();
+            let cnt = data.entry(site.to_string()).or_default().entry(source_id.to_string()).or_insert(0);
+            *cnt += 1;
+            *cnt
+        };
+        self.save();
+        cnt
+    }
+    pub fn clear(&self, site: &str, source_id: &str) {
+        let removed = self.data.borrow_mut().get_mut(site).and_then(|f| f.remove(source_id)).is_some();
+        if removed {
+            self.save();
+        }
+    }
+    fn save(&self) {
+        let s = match serde_json::to_string(&*self.data.borrow()) {
+            Err(e) => panic!("Download attempts format error: {}", e),
+            Ok(s) => s
+        };
+        if let Some(parent) = self.path.parent() {
+            if let Err(e) = fs::create_dir_all(parent) {
+                panic!("Cannot create download attempts dir {}: {}", parent.to_str().unwrap(), e)
+            }
+        }
+        if let Err(e) = fs::write(&self.path, s) {
+            panic!("Cannot write download attempts {}: {}", self.path.to_str().unwrap(), e)
+        }
+    }
+}
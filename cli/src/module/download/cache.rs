@@ -0,0 +1,94 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+use crate::module::config::LocalConfig;
+
+/// 下载的原始响应缓存。每个来源数据缓存为一个文件，其中按url记录此来源数据的下载过程中请求到的所有原始响应。
+pub struct ResponseCache {
+    cache_path: PathBuf
+}
+
+impl ResponseCache {
+    pub fn new(config: &LocalConfig) -> ResponseCache {
+        ResponseCache::from_path(config.work_path.appdata_path.join("cache/download"))
+    }
+    pub fn from_path(cache_path: PathBuf) -> ResponseCache {
+        ResponseCache { cache_path }
+    }
+    pub fn exists(&self, site: &str, source_id: &str) -> bool {
+        self.file_path(site, source_id).is_file()
+    }
+    /// 读取缓存的响应。缓存无法读取或已损坏时视作没有缓存，重新下载后会覆盖它。
+    pub fn load(&self, site: &str, source_id: &str) -> Option<HashMap<String, String>> {
+        let text = fs::read_to_string(self.file_path(site, source_id)).ok()?;
+        serde_json::from_str(&text).ok()
+    }
+    pub fn save(&self, site: &str, source_id: &str, responses: &HashMap<String, String>) {
+        let path = self.file_path(site, source_id);
+        let s = match serde_json::to_string(responses) {
+            Err(e) => panic!("Download cache format error: {}", e),
+            Ok(s) => s
+        };
+        if let Some(parent) = path.parent() {
+            if let Err(e) = fs::create_dir_all(parent) {
+                panic!("Cannot create download cache dir {}: {}", parent.to_str().unwrap(), e)
+            }
+        }
+        if let Err(e) = fs::write(&path, s) {
+            panic!("Cannot write download cache {}: {}", path.to_str().unwrap(), e)
+        }
+    }
+    fn file_path(&self, site: &str, source_id: &str) -> PathBuf {
+        self.cache_path.join(escape_file_name(site)).join(format!("{}.json", escape_file_name(source_id)))
+    }
+}
+
+/// 转义名称中的路径分隔符，使来源数据的名称不能指向缓存目录之外。
+fn escape_file_name(name: &str) -> String {
+    let escaped = name.replace('%', "%25").replace('/', "%2F").replace('\\', "%5C");
+    if escaped == "." || escaped == ".." { escaped.replace('.', "%2E") }else{ escaped }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_cache(name: &str) -> ResponseCache {
+        let cache_path = std::env::temp_dir().join(format!("hedge-cli-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&cache_path);
+        ResponseCache { cache_path }
+    }
+
+    #[test]
+    fn save_and_load() {
+        let cache = temp_cache("save");
+        let responses = HashMap::from([("https://example.com/1".to_string(), "[]".to_string())]);
+        assert!(!cache.exists("site", "1"));
+        cache.save("site", "1", &responses);
+        assert!(cache.exists("site", "1"));
+        assert_eq!(cache.load("site", "1"), Option::Some(responses));
+        fs::remove_dir_all(&cache.cache_path).unwrap();
+    }
+
+    #[test]
+    fn corrupt_cache_is_missed() {
+        let cache = temp_cache("corrupt");
+        let path = cache.file_path("site", "1");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "{not json").unwrap();
+        assert_eq!(cache.load("site", "1"), Option::None);
+        //目录无法作为文件读取
+        fs::remove_file(&path).unwrap();
+        fs::create_dir_all(&path).unwrap();
+        assert_eq!(cache.load("site", "1"), Option::None);
+        fs::remove_dir_all(&cache.cache_path).unwrap();
+    }
+
+    #[test]
+    fn source_id_stays_in_cache_dir() {
+        let cache = temp_cache("escape");
+        for id in ["../1", "a/b", "a\\b", "..", "100%"] {
+            let path = cache.file_path("site", id);
+            assert_eq!(path.parent().unwrap(), cache.cache_path.join("site"), "{}", id);
+        }
+        assert_ne!(cache.file_path("site", "a/b"), cache.file_path("site", "a%2Fb"));
+    }
+}
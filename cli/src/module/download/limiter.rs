@@ -0,0 +1,118 @@
+use std::{sync::Mutex, time::{Duration, Instant}};
+
+/// 令牌桶限流器。每个site持有一个，限制对此site的所有请求的速率。
+pub struct RateLimiter {
+    capacity: f64,
+    refill_per_sec: f64,
+    state: Mutex<RateLimiterState>
+}
+
+struct RateLimiterState {
+    tokens: f64,
+    last_refill: Instant,
+    blocked_until: Option<Instant>
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_minute: u32, burst: u32) -> RateLimiter {
+        let capacity = burst.max(1) as f64;
+        RateLimiter {
+            capacity,
+            refill_per_sec: requests_per_minute.max(1) as f64 / 60.0,
+            state: Mutex::new(RateLimiterState { tokens: capacity, last_refill: Instant::now(), blocked_until: Option::None })
+        }
+    }
+    /// 等待直到取得一个令牌。
+    pub async fn acquire(&self) {
+        loop {
+            let waiting = self.try_acquire();
+            if let Some(waiting) = waiting {
+                async_std::task::sleep(waiting).await;
+            }else{
+                return
+            }
+        }
+    }
+    /// 在接下来的一段时间内阻塞此site的所有请求，并清空令牌。用于处理429/5xx响应的退避。
+    pub fn block(&self, duration: Duration) {
+        let mut state = self.state.lock().unwrap();
+        let until = Instant::now() + duration;
+        if !matches!(state.blocked_until, Some(b) if b >= until) {
+            state.blocked_until = Option::Some(until);
+        }
+        state.tokens = 0.0;
+    }
+    fn try_acquire(&self) -> Option<Duration> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        if let Some(blocked_until) = state.blocked_until {
+            if blocked_until > now {
+                return Option::Some(blocked_until - now)
+            }
+            state.blocked_until = Option::None;
+            state.last_refill = blocked_until;
+        }
+        let elapsed = now.duration_since(state.last_refill).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        state.last_refill = now;
+        if state.tokens >= 1.0 {
+            state.tokens -= 1.0;
+            Option::None
+        }else{
+            Option::Some(Duration::from_secs_f64((1.0 - state.tokens) / self.refill_per_sec))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn burst_then_wait() {
+        let limiter = RateLimiter::new(60, 3);
+        for _ in 0..3 {
+            assert!(limiter.try_acquire().is_none());
+        }
+        let waiting = limiter.try_acquire().unwrap();
+        assert!(waiting > Duration::from_millis(900) && waiting <= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn refill_up_to_capacity() {
+        let limiter = RateLimiter::new(60, 2);
+        //从过去开始计算补充，令牌数不会超过容量
+        limiter.state.lock().unwrap().last_refill = Instant::now() - Duration::from_secs(10);
+        assert!(limiter.try_acquire().is_none());
+        assert!(limiter.try_acquire().is_none());
+        assert!(limiter.try_acquire().is_some());
+
+        limiter.state.lock().unwrap().last_refill = Instant::now() - Duration::from_millis(1500);
+        assert!(limiter.try_acquire().is_none());
+        assert!(limiter.try_acquire().is_some());
+    }
+
+    #[test]
+    fn block_clears_tokens() {
+        let limiter = RateLimiter::new(600, 5);
+        limiter.block(Duration::from_secs(30));
+        let waiting = limiter.try_acquire().unwrap();
+        assert!(waiting > Duration::from_secs(29) && waiting <= Duration::from_secs(30));
+        //更短的阻塞不会缩短已有的阻塞
+        limiter.block(Duration::from_secs(1));
+        assert!(limiter.try_acquire().unwrap() > Duration::from_secs(29));
+
+        //阻塞结束后令牌从零开始补充
+        limiter.state.lock().unwrap().blocked_until = Option::Some(Instant::now() - Duration::from_millis(150));
+        assert!(limiter.try_acquire().is_none());
+        assert!(limiter.try_acquire().is_some());
+    }
+
+    #[test]
+    fn zero_config_is_clamped() {
+        let limiter = RateLimiter::new(0, 0);
+        assert!(limiter.try_acquire().is_none());
+        let waiting = limiter.try_acquire().unwrap();
+        assert!(waiting > Duration::from_secs(59) && waiting <= Duration::from_secs(60));
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use crate::module::{api::source_data::SourceDataDetailRes, config::{MergePolicy, MergeStrategy}};
+use super::{DownloadResult, DownloadTag, DownloadBook};
+
+
+impl MergePolicy {
+    /// 是否所有字段都是replace。此时不需要取得现有的值。
+    pub fn is_replace(&self) -> bool {
+        [self.title, self.description, self.tags, self.books, self.relations, self.additional_info].iter().all(|s| *s == MergeStrategy::Replace)
+    }
+}
+
+impl DownloadResult {
+    /// 按合并策略，对照现有的来源数据计算需要提交的结果。结果中为None的字段不会被提交，因此服务器保持现有的值。
+    pub fn merge(&self, existing: &SourceDataDetailRes, policy: &MergePolicy) -> DownloadResult {
+        let existing_tags: Vec<DownloadTag> = existing.tags.iter().map(|t| DownloadTag { code: t.code.clone(), name: t.name.clone(), other_name: t.other_name.clone(), tag_type: Option::Some(t.tag_type.clone()) }).collect();
+        let existing_books: Vec<DownloadBook> = existing.books.iter().map(|b| DownloadBook { code: b.code.clone(), title: if b.title.is_empty() { Option::None }else{ Option::Some(b.title.clone()) }, other_title: b.other_title.clone() }).collect();
+        let existing_relations: Vec<i64> = existing.relations.iter().filter_map(|r| r.parse().ok()).collect();
+        let existing_info: HashMap<String, String> = existing.additional_info.iter().map(|f| (f.field.clone(), f.value.clone())).collect();
+
+        DownloadResult {
+            title: merge_str(&self.title, &existing.title, policy.title),
+            description: merge_str(&self.description, &existing.description, policy.description),
+            tags: merge_list(&self.tags, &existing_tags, policy.tags, |e, n| e.code == n.code && (n.tag_type.is_none() || e.tag_type == n.tag_type)),
+            books: merge_list(&self.books, &existing_books, policy.books, |e, n| e.code == n.code),
+            relations: merge_list(&self.relations, &existing_relations, policy.relations, |e, n| e == n),
+            additional_info: match &self.additional_info {
+                None => Option::None,
+                Some(info) => match policy.additional_info {
+                    MergeStrategy::Replace => Option::Some(info.clone()),
+                    MergeStrategy::KeepExisting => if existing_info.is_empty() { Option::Some(info.clone()) }else{ Option::None },
+                    MergeStrategy::PreferNonEmpty => if info.is_empty() { Option::None }else{ Option::Some(info.clone()) },
+                    MergeStrategy::Union => {
+                        //additional info是整体覆盖的，因此需要连同现有的字段一起提交
+                        let mut ret = existing_info.clone();
+                        for (k, v) in info {
+                            ret.entry(k.clone()).or_insert_with(|| v.clone());
+                        }
+                        Option::Some(ret)
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn merge_str(new: &Option<String>, existing: &str, strategy: MergeStrategy) -> Option<String> {
+    let new = new.as_ref()?;
+    match strategy {
+        MergeStrategy::Replace => Option::Some(new.clone()),
+        MergeStrategy::KeepExisting => if existing.is_empty() { Option::Some(new.clone()) }else{ Option::None },
+        MergeStrategy::Union | MergeStrategy::PreferNonEmpty => if new.is_empty() { Option::None }else{ Option::Some(new.clone()) }
+    }
+}
+
+fn merge_list<T: Clone, F: Fn(&T, &T) -> bool>(new: &Option<Vec<T>>, existing: &[T], strategy: MergeStrategy, same: F) -> Option<Vec<T>> {
+    let new = new.as_ref()?;
+    match strategy {
+        MergeStrategy::Replace => Option::Some(new.clone()),
+        MergeStrategy::KeepExisting => if existing.is_empty() { Option::Some(new.clone()) }else{ Option::None },
+        MergeStrategy::PreferNonEmpty => if new.is_empty() { Option::None }else{ Option::Some(new.clone()) },
+        MergeStrategy::Union => {
+            let mut ret = existing.to_vec();
+            for n in new {
+                if !ret.iter().any(|e| same(e, n)) {
+                    ret.push(n.clone());
+                }
+            }
+            Option::Some(ret)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use super::*;
+
+    fn existing(title: &str, tags: Value, relations: Value, additional_info: Value) -> SourceDataDetailRes {
+        serde_json::from_value(json!({
+            "sourceSite": "pixiv", "sourceSiteName": "Pixiv", "sourceId": "1", "title": title, "description": "",
+            "tags": tags, "books": [{"code": "b1", "title": "", "otherTitle": null}], "relations": relations, "links": [],
+            "additionalInfo": additional_info, "empty": false, "status": "EDITED", "publishTime": null,
+            "createTime": "2024-01-01T00:00:00Z", "updateTime": "2024-01-01T00:00:00Z"
+        })).unwrap()
+    }
+
+    fn tag(code: &str, tag_type: Option<&str>) -> DownloadTag {
+        DownloadTag { code: code.to_string(), name: Option::None, other_name: Option::None, tag_type: tag_type.map(str::to_string) }
+    }
+
+    fn policy(strategy: MergeStrategy) -> MergePolicy {
+        MergePolicy { title: strategy, description: strategy, tags: strategy, books: strategy, relations: strategy, additional_info: strategy }
+    }
+
+    #[test]
+    fn merge_str_strategies() {
+        let new = Option::Some("new".to_string());
+        let empty = Option::Some(String::new());
+        assert_eq!(merge_str(&new, "old", MergeStrategy::Replace), new);
+        assert_eq!(merge_str(&empty, "old", MergeStrategy::Replace), empty);
+        assert_eq!(merge_str(&new, "old", MergeStrategy::KeepExisting), Option::None);
+        assert_eq!(merge_str(&new, "", MergeStrategy::KeepExisting), new);
+        assert_eq!(merge_str(&empty, "old", MergeStrategy::PreferNonEmpty), Option::None);
+        assert_eq!(merge_str(&new, "old", MergeStrategy::PreferNonEmpty), new);
+        assert_eq!(merge_str(&empty, "old", MergeStrategy::Union), Option::None);
+        assert_eq!(merge_str(&Option::None, "", MergeStrategy::Replace), Option::None);
+    }
+
+    #[test]
+    fn merge_list_strategies() {
+        let same = |a: &i64, b: &i64| a == b;
+        let new = Option::Some(vec![2, 3]);
+        assert_eq!(merge_list(&new, &[1, 2], MergeStrategy::Replace, same), Option::Some(vec![2, 3]));
+        assert_eq!(merge_list(&new, &[1, 2], MergeStrategy::KeepExisting, same), Option::None);
+        assert_eq!(merge_list(&new, &[], MergeStrategy::KeepExisting, same), Option::Some(vec![2, 3]));
+        assert_eq!(merge_list(&Option::Some(vec![]), &[1], MergeStrategy::PreferNonEmpty, same), Option::None);
+        assert_eq!(merge_list(&new, &[1], MergeStrategy::PreferNonEmpty, same), Option::Some(vec![2, 3]));
+        assert_eq!(merge_list(&new, &[1, 2], MergeStrategy::Union, same), Option::Some(vec![1, 2, 3]));
+        assert_eq!(merge_list(&Option::None, &[1, 2], MergeStrategy::Union, same), Option::None);
+    }
+
+    #[test]
+    fn merge_union_tags_by_code_and_type() {
+        let existing = existing("old", json!([{"code": "a", "type": "artist", "name": null, "otherName": null}, {"code": "b", "type": "general", "name": null, "otherName": null}]), json!(["1"]), json!([]));
+        let result = DownloadResult {
+            title: Option::None,
+            description: Option::None,
+            //没有类型的a与现有的a视为同一项；类型不同的b视为不同的项
+            tags: Option::Some(vec![tag("a", Option::None), tag("b", Option::Some("character")), tag("c", Option::None)]),
+            books: Option::None,
+            relations: Option::Some(vec![1, 2]),
+            additional_info: Option::None
+        };
+        let merged = result.merge(&existing, &policy(MergeStrategy::Union));
+        let tags: Vec<(String, Option<String>)> = merged.tags.unwrap().into_iter().map(|t| (t.code, t.tag_type)).collect();
+        assert_eq!(tags, vec![
+            ("a".to_string(), Option::Some("artist".to_string())),
+            ("b".to_string(), Option::Some("general".to_string())),
+            ("b".to_string(), Option::Some("character".to_string())),
+            ("c".to_string(), Option::None)
+        ]);
+        assert_eq!(merged.relations, Option::Some(vec![1, 2]));
+    }
+
+    #[test]
+    fn merge_additional_info() {
+        let existing = existing("old", json!([]), json!([]), json!([{"field": "md5", "label": "MD5", "value": "old"}]));
+        let info = HashMap::from([("md5".to_string(), "new".to_string()), ("size".to_string(), "1".to_string())]);
+        let result = DownloadResult { title: Option::Some("new".to_string()), description: Option::None, tags: Option::None, books: Option::None, relations: Option::None, additional_info: Option::Some(info.clone()) };
+
+        let merged = result.merge(&existing, &policy(MergeStrategy::Union));
+        assert_eq!(merged.additional_info, Option::Some(HashMap::from([("md5".to_string(), "old".to_string()), ("size".to_string(), "1".to_string())])));
+        assert_eq!(merged.title, Option::Some("new".to_string()));
+        assert_eq!(result.merge(&existing, &policy(MergeStrategy::Replace)).additional_info, Option::Some(info));
+        let merged = result.merge(&existing, &policy(MergeStrategy::KeepExisting));
+        assert_eq!((merged.title, merged.additional_info), (Option::None, Option::None));
+    }
+
+    #[test]
+    fn replace_policy() {
+        assert!(MergePolicy::default().is_replace());
+        assert!(!policy(MergeStrategy::Union).is_replace());
+    }
+}
@@ -1,64 +1,120 @@
 mod sankakucomplex;
+mod limiter;
+pub mod attempts;
+pub mod cache;
+mod merge;
 
-use std::{time::Duration, error::Error, collections::HashMap};
-use reqwest::{Method, IntoUrl, RequestBuilder, Proxy, Response};
+use std::{time::Duration, error::Error, collections::HashMap, cell::RefCell};
+use reqwest::{Method, IntoUrl, RequestBuilder, Proxy, Response, StatusCode, header::RETRY_AFTER};
 use serde::Serialize;
 use crate::utils::error::ApplicationError;
-use super::{config::LocalConfig, api::source_data::{SourceDataUpdateForm, SourceTagForm, SourceBookForm, AdditionalInfoForm}};
+use super::{config::LocalConfig, api::source_data::{SourceDataUpdateForm, SourceTagForm, SourceBookForm, AdditionalInfoForm}, api::bulk::{SourceDataBulkForm, SourceTagForm as BulkSourceTagForm, SourceBookForm as BulkSourceBookForm, AdditionalInfoForm as BulkAdditionalInfoForm}};
 use sankakucomplex::download_for_sankakucomplex;
+use limiter::RateLimiter;
+use cache::ResponseCache;
 
 
 pub struct DownloadModule {
-    adapter: Adapter,
-    available_sites: HashMap<String, String>
+    available_sites: HashMap<String, (String, Adapter)>,
+    cache: ResponseCache,
+    cache_mode: CacheMode
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CacheMode {
+    /// 优先使用缓存的响应，缓存不存在时请求网络，并写入缓存。
+    Prefer,
+    /// 总是请求网络，并用新的响应覆盖缓存。
+    Refresh,
+    /// 只使用缓存的响应，不请求网络。
+    Offline
 }
 
 impl DownloadModule {
-    pub fn new(config: &LocalConfig) -> DownloadModule {
+    pub fn new(config: &LocalConfig, cache_mode: CacheMode) -> DownloadModule {
         let mut client_builder = reqwest::Client::builder();
         if let Some(proxy) = &config.download.proxy {
             client_builder = client_builder.proxy(Proxy::all(proxy).unwrap());
         }
-        let adapter = Adapter {
-            client: client_builder.build().unwrap(),
-            timeout: config.download.timeout_interval.unwrap_or(20),
-            waiting: config.download.waiting_interval.unwrap_or(8)
-        };
-        let mut available_sites: HashMap<String, String> = HashMap::new();
+        let client = client_builder.build().unwrap();
+        let timeout = config.download.timeout_interval.unwrap_or(20);
+        //未配置rate时，沿用waiting_interval的语义：每隔waiting秒允许一次请求
+        let default_rate = (60 / config.download.waiting_interval.unwrap_or(8).max(1)).max(1) as u32;
+        let mut available_sites: HashMap<String, (String, Adapter)> = HashMap::new();
         for ele in &config.download.available_sites {
-            available_sites.insert(ele.site.clone(), ele.rule.clone());
+            let adapter = Adapter {
+                client: client.clone(),
+                timeout,
+                backoff: ele.backoff.unwrap_or(30),
+                limiter: RateLimiter::new(ele.rate.unwrap_or(default_rate), ele.burst.unwrap_or(1))
+            };
+            available_sites.insert(ele.site.clone(), (ele.rule.clone(), adapter));
         }
 
-        DownloadModule { 
-            adapter,
-            available_sites
-        }
+        DownloadModule { available_sites, cache: ResponseCache::new(config), cache_mode }
+    }
+    pub fn is_cached(&self, site: &str, source_id: &str) -> bool {
+        self.cache.exists(site, source_id)
     }
     pub async fn download(&self, site: &str, source_id: &str, _additional_info: Option<&HashMap<String, String>>) -> Result<(DownloadResult, DownloadAttachInfo), Box<dyn Error>> {
-        if let Some(rule) = self.available_sites.get(site) {
-            if rule == "sankakucomplex" {
-                download_for_sankakucomplex(&self.adapter, source_id.parse().unwrap()).await
+        if let Some((rule, adapter)) = self.available_sites.get(site) {
+            let cached = if self.cache_mode == CacheMode::Refresh { Option::None }else{ self.cache.load(site, source_id) };
+            let session = Session { adapter, cached, offline: self.cache_mode == CacheMode::Offline, recorded: RefCell::new(HashMap::new()) };
+            let result: Result<(DownloadResult, DownloadAttachInfo), Box<dyn Error>> = if rule == "sankakucomplex" {
+                download_for_sankakucomplex(&session, source_id.parse().unwrap()).await
             }else{
                 Result::Err(Box::new(ApplicationError::new(&format!("Unsupported rule type {}.", rule))))
+            };
+            //即使解析失败，也保存已取得的响应，以便修正解析规则后重新解析
+            let recorded = session.recorded.into_inner();
+            if !recorded.is_empty() {
+                let mut responses = session.cached.unwrap_or_default();
+                responses.extend(recorded);
+                self.cache.save(site, source_id, &responses);
             }
+            result
         }else{
             Result::Err(Box::new(ApplicationError::new(&format!("Site {} not configured in available sites.", site))))
         }
     }
-    pub async fn wait(&self, time_cost: i64) {
-        let cost = (time_cost / 1000) as u64;
-        let waiting = if self.adapter.waiting - cost > self.adapter.waiting / 2 { self.adapter.waiting - cost }else{ self.adapter.waiting / 2 };
-        async_std::task::sleep(Duration::from_secs(waiting)).await;
+}
+
+/// 一个来源数据的一次下载过程。所有请求都经由此处，以便使用和记录缓存的响应。
+pub struct Session<'t> {
+    adapter: &'t Adapter,
+    cached: Option<HashMap<String, String>>,
+    offline: bool,
+    recorded: RefCell<HashMap<String, String>>
+}
+
+impl <'t> Session<'t> {
+    async fn req_text(&self, url: &str) -> Result<(String, i32), Box<dyn Error>> {
+        if let Some(text) = self.cached.as_ref().and_then(|f| f.get(url)) {
+            return Result::Ok((text.clone(), 0))
+        }
+        if self.offline {
+            return Result::Err(Box::new(ApplicationError::new(&format!("Response of {} is not cached.", url))))
+        }
+        let (res, retry_cnt) = self.adapter.try_req(Method::GET, url).await?;
+        let success = res.status().is_success();
+        let text = res.text().await?;
+        if success {
+            self.recorded.borrow_mut().insert(url.to_string(), text.clone());
+        }
+        Result::Ok((text, retry_cnt))
     }
 }
 
 pub struct Adapter {
     client: reqwest::Client,
-    waiting: u64,
-    timeout: u64
+    timeout: u64,
+    backoff: u64,
+    limiter: RateLimiter
 }
 
 impl Adapter {
+    const MAX_ATTEMPTS: i32 = 3;
+
     fn req<U : IntoUrl>(&self, method: Method, url: U) -> RequestBuilder {
         self.client.request(method, url)
             .header("User-Agent", "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_4) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/80.0.3987.163 Safari/537.36")
@@ -69,23 +125,41 @@ impl Adapter {
     }
     async fn try_req<U : IntoUrl + Copy>(&self, method: Method, url: U) -> Result<(Response, i32), Box<dyn Error>> {
         let mut retry_cnt = 0;
-        let mut error: Option<reqwest::Error> = Option::None;
-        while retry_cnt < 3 {
-            let response = self.req(method.clone(), url).send().await;
-            match response {
-                Ok(ok) => return Result::Ok((ok, retry_cnt)),
+        loop {
+            self.limiter.acquire().await;
+            match self.req(method.clone(), url).send().await {
+                Ok(res) => {
+                    let status = res.status();
+                    if status != StatusCode::TOO_MANY_REQUESTS && !status.is_server_error() {
+                        return Result::Ok((res, retry_cnt))
+                    }
+                    if retry_cnt + 1 >= Self::MAX_ATTEMPTS {
+                        return Result::Err(Box::new(ApplicationError::new(&format!("Request failed with status {}.", status))))
+                    }
+                    //429/5xx时，此site的所有请求都进入退避；优先遵循服务器给出的Retry-After
+                    let waiting = retry_after(&res).unwrap_or_else(|| Duration::from_secs(self.backoff << retry_cnt));
+                    self.limiter.block(waiting);
+                },
                 Err(e) => {
-                    if e.is_connect() || e.is_timeout() {
-                        error = Option::Some(e);
-                        retry_cnt += 1;
+                    if (e.is_connect() || e.is_timeout()) && retry_cnt + 1 < Self::MAX_ATTEMPTS {
+                        async_std::task::sleep(Duration::from_secs(1)).await;
                     }else{
                         return Result::Err(Box::new(e));
                     }
                 }
             }
-            async_std::task::sleep(Duration::from_secs(1)).await;
+            retry_cnt += 1;
         }
-        return Result::Err(Box::new(error.unwrap()));
+    }
+}
+
+fn retry_after(res: &Response) -> Option<Duration> {
+    let value = res.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        Option::Some(Duration::from_secs(seconds))
+    }else{
+        let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+        Option::Some((date.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().unwrap_or(Duration::ZERO))
     }
 }
 
@@ -94,7 +168,7 @@ pub struct DownloadAttachInfo {
     pub time_cost: i64
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, Clone)]
 pub struct DownloadResult {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub title: Option<String>,
@@ -110,7 +184,7 @@ pub struct DownloadResult {
     pub additional_info: Option<HashMap<String, String>>
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, Clone)]
 pub struct DownloadTag {
     pub code: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -121,7 +195,7 @@ pub struct DownloadTag {
     pub tag_type: Option<String>
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, Clone)]
 pub struct DownloadBook {
     pub code: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -142,6 +216,21 @@ impl DownloadResult {
             additional_info: self.additional_info.as_ref().map(|info| info.iter().map(|(k, v)| AdditionalInfoForm { field: k.clone(), value: v.clone() }).collect())
         }
     }
+    pub fn to_bulk_form(&self, site: &str, id: &str) -> SourceDataBulkForm {
+        SourceDataBulkForm {
+            source_site: site.to_string(),
+            source_id: id.to_string(),
+            status: Option::None,
+            title: self.title.clone(),
+            description: self.description.clone(),
+            tags: self.tags.as_ref().map(|tags| tags.iter().map(|tag| BulkSourceTagForm { code: tag.code.clone(), name: tag.name.clone(), other_name: tag.other_name.clone(), tag_type: tag.tag_type.clone() }).collect()),
+            books: self.books.as_ref().map(|books| books.iter().map(|book| BulkSourceBookForm { code: book.code.clone(), title: book.title.clone(), other_title: book.other_title.clone() }).collect()),
+            relations: self.relations.as_ref().map(|relations| relations.iter().map(|r| r.to_string()).collect()),
+            links: Option::None,
+            additional_info: self.additional_info.as_ref().map(|info| info.iter().map(|(k, v)| BulkAdditionalInfoForm { field: k.clone(), value: v.clone() }).collect()),
+            publish_time: Option::None
+        }
+    }
     pub fn info(&self) -> String {
         let title = self.title.as_ref().map(|t| format!("<{}>", t)).unwrap_or("".to_string());
         let info = self.additional_info.as_ref().map(|f| format!("{} {} {}", '{', f.iter().map(|(k, v)| format!("{} = {}", k, v)).collect::<Vec<String>>().join(", "), '}')).unwrap_or("".to_string());
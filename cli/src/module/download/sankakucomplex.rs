@@ -1,12 +1,10 @@
 use std::error::Error;
-use reqwest::Method;
-use super::{Adapter, DownloadResult, DownloadAttachInfo, DownloadTag, DownloadBook};
+use super::{Session, DownloadResult, DownloadAttachInfo, DownloadTag, DownloadBook};
 
-pub async fn download_for_sankakucomplex(adapter: &Adapter, id: i64) -> Result<(DownloadResult, DownloadAttachInfo), Box<dyn Error>> {
+pub async fn download_for_sankakucomplex(session: &Session<'_>, id: i64) -> Result<(DownloadResult, DownloadAttachInfo), Box<dyn Error>> {
     let start_timestamp = chrono::Utc::now().timestamp_millis();
     let url = format!("https://capi-v2.sankakucomplex.com/posts?lang=en&page=1&limit=1&tags=id_range:{id}");
-    let (res, retry_cnt) = adapter.try_req(Method::GET, url.as_str()).await?;
-    let text = res.text().await?;
+    let (text, retry_cnt) = session.req_text(url.as_str()).await?;
     let mut retry_sum_cnt = retry_cnt;
 
     let value: serde_json::Value = serde_json::from_str(text.as_str())?;
@@ -34,7 +32,7 @@ pub async fn download_for_sankakucomplex(adapter: &Adapter, id: i64) -> Result<(
     };
 
     let books: Option<Vec<DownloadBook>> = if let Some(_) = obj.get("in_visible_pool").filter(|b| b.is_boolean() && b.as_bool().unwrap()) {
-        let (res, retry_cnt) = download_for_book(adapter, id).await?;
+        let (res, retry_cnt) = download_for_book(session, id).await?;
         retry_sum_cnt += retry_cnt;
         Option::Some(res)
     }else{
@@ -42,7 +40,7 @@ pub async fn download_for_sankakucomplex(adapter: &Adapter, id: i64) -> Result<(
     };
 
     let children: Option<Vec<i64>> = if let Some(_) = obj.get("has_children").filter(|b| b.is_boolean() && b.as_bool().unwrap()) {
-        let (res, retry_cnt) = download_for_children(adapter, id).await?;
+        let (res, retry_cnt) = download_for_children(session, id).await?;
         retry_sum_cnt += retry_cnt;
         Option::Some(res)
     }else{
@@ -77,10 +75,9 @@ pub async fn download_for_sankakucomplex(adapter: &Adapter, id: i64) -> Result<(
     Result::Ok((ret, info))
 }
 
-async fn download_for_children(adapter: &Adapter, id: i64) -> Result<(Vec<i64>, i32), Box<dyn Error>> {
+async fn download_for_children(session: &Session<'_>, id: i64) -> Result<(Vec<i64>, i32), Box<dyn Error>> {
     let url = format!("https://capi-v2.sankakucomplex.com/posts?lang=en&page=1&limit=40&tags=parent:{id}");
-    let (res, retry_cnt) = adapter.try_req(Method::GET, url.as_str()).await?;
-    let text = res.text().await?;
+    let (text, retry_cnt) = session.req_text(url.as_str()).await?;
 
     let value: serde_json::Value = serde_json::from_str(text.as_str())?;
     let arr = value.as_array().unwrap();
@@ -89,10 +86,9 @@ async fn download_for_children(adapter: &Adapter, id: i64) -> Result<(Vec<i64>,
     Result::Ok((ret, retry_cnt))
 }
 
-async fn download_for_book(adapter: &Adapter, id: i64) -> Result<(Vec<DownloadBook>, i32), Box<dyn Error>> {
+async fn download_for_book(session: &Session<'_>, id: i64) -> Result<(Vec<DownloadBook>, i32), Box<dyn Error>> {
     let url = format!("https://capi-v2.sankakucomplex.com/post/{id}/pools?lang=en");
-    let (res, retry_cnt) = adapter.try_req(Method::GET, url.as_str()).await?;
-    let text = res.text().await?;
+    let (text, retry_cnt) = session.req_text(url.as_str()).await?;
 
     let value: serde_json::Value = serde_json::from_str(text.as_str())?;
     let arr = value.as_array().unwrap();
@@ -122,4 +118,60 @@ fn get_tag_types(type_code: i64) -> &'static str {
         5 => "genre",      // 特别内容
         _ => panic!("Unknown tag type {}.", type_code)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, collections::HashMap, path::PathBuf};
+    use super::*;
+    use super::super::{Adapter, cache::ResponseCache, limiter::RateLimiter};
+
+    /// (code, name, other_name, type)
+    type TagFields = (String, Option<String>, Option<String>, Option<String>);
+
+    /// 固定的响应缓存，格式与下载时写入的缓存文件相同。
+    fn fixtures() -> ResponseCache {
+        ResponseCache::from_path(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/module/download/fixtures"))
+    }
+
+    async fn download(id: i64) -> Result<DownloadResult, Box<dyn Error>> {
+        let adapter = Adapter { client: reqwest::Client::new(), timeout: 1, backoff: 1, limiter: RateLimiter::new(60, 1) };
+        let cached = fixtures().load("sankakucomplex", &id.to_string());
+        let session = Session { adapter: &adapter, cached, offline: true, recorded: RefCell::new(HashMap::new()) };
+        download_for_sankakucomplex(&session, id).await.map(|(r, _)| r)
+    }
+
+    #[tokio::test]
+    async fn parse_post() {
+        let result = download(30000001).await.unwrap();
+        let tags: Vec<TagFields> = result.tags.unwrap().into_iter().map(|t| (t.code, t.name, t.other_name, t.tag_type)).collect();
+        assert_eq!(tags, vec![
+            ("hatsune miku".to_string(), Option::Some("hatsune miku".to_string()), Option::Some("初音ミク".to_string()), Option::Some("character".to_string())),
+            ("vocaloid".to_string(), Option::Some("vocaloid".to_string()), Option::Some("ボーカロイド".to_string()), Option::Some("copyright".to_string())),
+            ("long hair".to_string(), Option::Some("long hair".to_string()), Option::None, Option::Some("general".to_string())),
+            ("kei (artist)".to_string(), Option::Some("kei (artist)".to_string()), Option::None, Option::Some("artist".to_string()))
+        ]);
+        let books: Vec<(String, Option<String>, Option<String>)> = result.books.unwrap().into_iter().map(|b| (b.code, b.title, b.other_title)).collect();
+        assert_eq!(books, vec![("5001".to_string(), Option::Some("Miku Collection".to_string()), Option::Some("ミクコレクション".to_string()))]);
+        assert_eq!(result.relations, Option::Some(vec![30000002, 30000003, 30000000]));
+    }
+
+    #[tokio::test]
+    async fn parse_post_without_pool_and_relations() {
+        let result = download(30000004).await.unwrap();
+        assert_eq!(result.tags.map(|t| t.len()), Option::Some(1));
+        assert!(result.books.is_none());
+        assert!(result.relations.is_none());
+    }
+
+    #[tokio::test]
+    async fn post_not_found() {
+        let e = download(30000009).await.err().unwrap();
+        assert_eq!(e.to_string(), "Post 30000009 not found.");
+    }
+
+    #[tokio::test]
+    async fn response_not_cached() {
+        let e = download(30000010).await.err().unwrap();
+        assert!(e.to_string().ends_with("is not cached."));
+    }
+}
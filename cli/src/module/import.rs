@@ -7,6 +7,7 @@ use serde_json::json;
 use super::server::{IdRes, ListResult, ServerManager};
 use tokio::fs::File;
 use tokio_util::codec::{BytesCodec, FramedRead};
+use crate::utils::deserialize::string_or_integer;
 
 pub struct ImportModule<'t> {
     server_manager: &'t ServerManager
@@ -80,10 +81,10 @@ impl OrderTimeType {
 
 #[derive(Deserialize, Serialize)]
 pub struct SourceDataPath {
-    #[serde(rename = "sourceSite", alias = "source_site", alias = "id")]
+    #[serde(rename = "sourceSite", alias = "source_site", alias = "site")]
     pub source_site: String,
-    #[serde(rename = "sourceId", alias = "source_id", alias = "id")]
-    pub source_id: i64,
+    #[serde(rename = "sourceId", alias = "source_id", alias = "id", deserialize_with = "string_or_integer")]
+    pub source_id: String,
     #[serde(rename = "sourcePart", alias = "source_part", alias = "part")]
     pub source_part: Option<i32>,
     #[serde(rename = "sourcePartName", alias = "source_part_name", alias = "part_name")]
@@ -5,4 +5,6 @@ pub mod server;
 pub mod import;
 pub mod api;
 pub mod download;
-pub mod connect;
\ No newline at end of file
+pub mod connect;
+pub mod connect_state;
+pub mod source_analyse;
\ No newline at end of file
@@ -0,0 +1,147 @@
+use regex::{Captures, Regex};
+use crate::module::api::setting::SourceAnalyseRule;
+
+/// 在本地执行来源分析规则，行为与服务端的SourceAnalyzeManager保持一致，用于在apply之前测试规则。
+/// 本地使用Rust的regex实现，它不支持Java正则中的环视、反向引用等特性，这类规则在本地会报告语法错误。
+pub struct SourceAnalyser<'t> {
+    rules: Vec<(&'t SourceAnalyseRule, Result<Regex, String>)>
+}
+
+pub struct SourceAnalyseResult {
+    pub rule_index: usize,
+    pub site: String,
+    pub id: String,
+    pub part: Option<i32>,
+    pub part_name: Option<String>,
+    pub extras: Vec<SourceAnalyseExtraResult>
+}
+
+pub struct SourceAnalyseExtraResult {
+    pub target: String,
+    pub value: String
+}
+
+impl <'t> SourceAnalyser<'t> {
+    pub fn new(rules: &'t [SourceAnalyseRule]) -> SourceAnalyser<'t> {
+        let rules = rules.iter().map(|r| (r, Regex::new(&r.regex).map_err(|e| format!("Pattern syntax error: {}", e)))).collect();
+        SourceAnalyser { rules }
+    }
+    /// 返回所有规则的正则编译错误。
+    pub fn compile_errors(&self) -> Vec<(usize, &'t SourceAnalyseRule, &str)> {
+        self.rules.iter().enumerate().filter_map(|(i, (r, regex))| regex.as_ref().err().map(|e| (i, *r, e.as_str()))).collect()
+    }
+    /// 按顺序尝试每条规则，返回第一条匹配的结果。与服务端一致，执行到有错误的规则时会直接返回错误。
+    pub fn analyse(&self, filename: &str) -> Result<Option<SourceAnalyseResult>, String> {
+        let text = match filename.rfind('.') {
+            Some(i) => &filename[..i],
+            None => filename
+        };
+        for (i, (rule, regex)) in self.rules.iter().enumerate() {
+            let regex = regex.as_ref().map_err(|_| format!("Rule #{} ({}): pattern cannot be compiled.", i + 1, rule.site))?;
+            if let Some(captures) = regex.captures(text) {
+                return analyse_one_rule(i, rule, regex, &captures).map(Option::Some).map_err(|e| format!("Rule #{} ({}): {}", i + 1, rule.site, e))
+            }
+        }
+        Result::Ok(Option::None)
+    }
+}
+
+fn analyse_one_rule(rule_index: usize, rule: &SourceAnalyseRule, regex: &Regex, captures: &Captures) -> Result<SourceAnalyseResult, String> {
+    let id = group_of(regex, captures, &rule.id_group)?.ok_or_else(|| format!("group '{}' not matched in regex.", rule.id_group))?;
+    let part = if let Some(part_group) = &rule.part_group {
+        let part = group_of(regex, captures, part_group)?.ok_or_else(|| format!("group '{}' not matched in regex.", part_group))?;
+        Option::Some(part.parse::<i32>().map_err(|_| "Some value cannot be convert to number.".to_string())?)
+    }else{
+        Option::None
+    };
+    let part_name = if let Some(part_name_group) = &rule.part_name_group { group_of(regex, captures, part_name_group)? }else{ Option::None };
+
+    let mut extras = Vec::new();
+    for extra in rule.extras.iter().flatten() {
+        let value = match group_of(regex, captures, &extra.group)? {
+            Some(v) => if extra.translate_underscore_to_space.unwrap_or(false) { v.replace('_', " ") }else{ v },
+            None => if extra.optional { continue }else{ return Result::Err(format!("group '{}' not matched in regex.", extra.group)) }
+        };
+        let target = match extra.target.as_str() {
+            "TITLE" => "title".to_string(),
+            "DESCRIPTION" => "description".to_string(),
+            "ADDITIONAL_INFO" => format!("additional info {}", extra.additional_info_field.as_ref().ok_or("additionalInfoField is required for ADDITIONAL_INFO extra.")?),
+            "TAG" => format!("tag [{}]", extra.tag_type.as_ref().ok_or("tagType is required for TAG extra.")?),
+            "BOOK" => "book".to_string(),
+            "RELATION" => "relation".to_string(),
+            t => return Result::Err(format!("Unknown extra target {}.", t))
+        };
+        extras.push(SourceAnalyseExtraResult { target, value });
+    }
+
+    Result::Ok(SourceAnalyseResult { rule_index, site: rule.site.clone(), id, part, part_name, extras })
+}
+
+/// group可以是序号或命名分组的名称。分组不存在时报错，分组未参与匹配时返回None。
+fn group_of(regex: &Regex, captures: &Captures, group: &str) -> Result<Option<String>, String> {
+    if let Ok(i) = group.parse::<usize>() {
+        if i >= regex.captures_len() {
+            return Result::Err(format!("group '{}' not exist in regex.", group))
+        }
+        Result::Ok(captures.get(i).map(|m| m.as_str().to_string()))
+    }else{
+        if !regex.capture_names().any(|n| n == Option::Some(group)) {
+            return Result::Err(format!("group '{}' not exist in regex.", group))
+        }
+        Result::Ok(captures.name(group).map(|m| m.as_str().to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::*;
+
+    fn rule(value: serde_json::Value) -> SourceAnalyseRule {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn rules() -> Vec<SourceAnalyseRule> {
+        vec![
+            rule(json!({"site": "pixiv", "regex": r"^(\d+)_p(\d+)$", "idGroup": "1", "partGroup": "2"})),
+            rule(json!({"site": "danbooru", "regex": r"^danbooru_(?P<id>\d+)(?:_(?P<title>\w+))?$", "idGroup": "id", "extras": [
+                {"group": "title", "target": "TITLE", "optional": true, "translateUnderscoreToSpace": true}
+            ]}))
+        ]
+    }
+
+    #[test]
+    fn analyse_first_matched_rule() {
+        let rules = rules();
+        let analyser = SourceAnalyser::new(&rules);
+        assert!(analyser.compile_errors().is_empty());
+
+        let result = analyser.analyse("10001_p2.jpg").unwrap().unwrap();
+        assert_eq!((result.rule_index, result.site.as_str(), result.id.as_str(), result.part), (0, "pixiv", "10001", Option::Some(2)));
+
+        let result = analyser.analyse("danbooru_42_a_b.png").unwrap().unwrap();
+        assert_eq!((result.rule_index, result.id.as_str(), result.part), (1, "42", Option::None));
+        assert_eq!(result.extras.iter().map(|e| (e.target.as_str(), e.value.as_str())).collect::<Vec<_>>(), vec![("title", "a b")]);
+
+        let result = analyser.analyse("danbooru_42.png").unwrap().unwrap();
+        assert!(result.extras.is_empty());
+
+        assert!(analyser.analyse("unknown.png").unwrap().is_none());
+    }
+
+    #[test]
+    fn analyse_rule_errors() {
+        let rules = vec![
+            rule(json!({"site": "a", "regex": r"^a(\w+)$", "idGroup": "1", "partGroup": "1"})),
+            rule(json!({"site": "b", "regex": r"^b(\d+)$", "idGroup": "2"})),
+            rule(json!({"site": "c", "regex": r"^c(\d+)$", "idGroup": "1", "extras": [{"group": "1", "target": "TAG", "optional": false}]})),
+            rule(json!({"site": "d", "regex": r"^(?<=d)(\d+)$", "idGroup": "1"}))
+        ];
+        let analyser = SourceAnalyser::new(&rules);
+        assert_eq!(analyser.compile_errors().iter().map(|(i, r, _)| (*i, r.site.as_str())).collect::<Vec<_>>(), vec![(3, "d")]);
+        assert_eq!(analyser.analyse("axyz").err().unwrap(), "Rule #1 (a): Some value cannot be convert to number.");
+        assert_eq!(analyser.analyse("b1").err().unwrap(), "Rule #2 (b): group '2' not exist in regex.");
+        assert_eq!(analyser.analyse("c1").err().unwrap(), "Rule #3 (c): tagType is required for TAG extra.");
+        assert_eq!(analyser.analyse("d1").err().unwrap(), "Rule #4 (d): pattern cannot be compiled.");
+    }
+}
@@ -0,0 +1,28 @@
+use serde::{Deserialize, Deserializer};
+use schemars::JsonSchema;
+
+/// 来源ID在服务端是字符串，但在手写的apply文件中通常直接写成数字。两种写法都接受。
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum StringOrInteger {
+    String(String),
+    Integer(i64)
+}
+
+impl From<StringOrInteger> for String {
+    fn from(value: StringOrInteger) -> Self {
+        match value {
+            StringOrInteger::String(s) => s,
+            StringOrInteger::Integer(i) => i.to_string()
+        }
+    }
+}
+
+pub fn string_or_integer<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    StringOrInteger::deserialize(deserializer).map(String::from)
+}
+
+pub fn option_vec_string_or_integer<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<String>>, D::Error> {
+    let v: Option<Vec<StringOrInteger>> = Option::deserialize(deserializer)?;
+    Result::Ok(v.map(|v| v.into_iter().map(String::from).collect()))
+}
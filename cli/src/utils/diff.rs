@@ -0,0 +1,57 @@
+use serde_json::Value;
+
+
+/// 整体覆盖的映射类型字段。这些字段按完整相等比较，其他对象只比较新值中给出的键。
+const EXACT_OBJECT_FIELDS: [&str; 3] = ["topicColors", "authorColors", "tagTypeMappings"];
+
+/// 一个字段的变化。
+pub struct FieldChange {
+    pub field: String,
+    pub old: Value,
+    pub new: Value
+}
+
+/// 对比表单与当前值。只对比表单中给出的顶层字段，返回所有有变化的字段。
+pub fn diff_fields(form: &Value, current: &Value, skip: &[&str]) -> Vec<FieldChange> {
+    let mut ret = Vec::new();
+    if let Value::Object(form) = form {
+        for (k, new) in form.iter() {
+            if skip.contains(&k.as_str()) { continue }
+            let old = current.get(k).unwrap_or(&Value::Null);
+            if !matches(new, old, EXACT_OBJECT_FIELDS.contains(&k.as_str())) {
+                ret.push(FieldChange { field: k.clone(), old: old.clone(), new: new.clone() });
+            }
+        }
+    }
+    ret
+}
+
+/// 判断新值是否与当前值一致。对象只比较新值给出的键；数组按元素一一匹配，不考虑顺序。
+pub fn matches(new: &Value, current: &Value, exact: bool) -> bool {
+    match (new, current) {
+        (Value::Object(n), Value::Object(c)) => {
+            if exact && n.len() != c.len() { return false }
+            n.iter().all(|(k, v)| matches(v, c.get(k).unwrap_or(&Value::Null), exact))
+        },
+        (Value::Array(n), Value::Array(c)) => {
+            if n.len() != c.len() { return false }
+            array_diff(n, c).0.is_empty()
+        },
+        (Value::Number(n), Value::Number(c)) => n.as_f64() == c.as_f64(),
+        _ => new == current
+    }
+}
+
+/// 对比两个数组，返回(新增的元素, 移除的元素)。每个当前元素最多被匹配一次。
+pub fn array_diff<'a, 'b>(new: &'a [Value], current: &'b [Value]) -> (Vec<&'a Value>, Vec<&'b Value>) {
+    let mut used = vec![false; current.len()];
+    let mut added = Vec::new();
+    for n in new {
+        match (0..current.len()).find(|i| !used[*i] && matches(n, &current[*i], false)) {
+            Some(i) => used[i] = true,
+            None => added.push(n)
+        }
+    }
+    let removed = current.iter().zip(used).filter(|(_, u)| !u).map(|(c, _)| c).collect();
+    (added, removed)
+}
@@ -0,0 +1,73 @@
+use regex::Regex;
+
+
+/// 文本中的位置，行号与列号都从1开始。
+pub type Location = (usize, usize);
+
+/// 将字节偏移量转换为行列号。
+pub fn offset_to_location(text: &str, offset: usize) -> Location {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    (before.matches('\n').count() + 1, before[line_start..].chars().count() + 1)
+}
+
+/// 在文本中查找第nth个(从0开始)名为keys之一的键，给出value时还要求其值为value，返回键所在的位置。
+/// 同时适用于json、yaml和toml，包括toml的[a.b]表头写法。
+pub fn locate_key(text: &str, keys: &[&str], value: Option<&str>, nth: usize) -> Option<Location> {
+    let keys = keys.iter().map(|k| regex::escape(k)).collect::<Vec<_>>().join("|");
+    let pattern = match value {
+        Some(v) => format!(r#"(?m)(?:^|[\s{{,\[.])["']?({})["']?\s*[:=]\s*["']?{}["']?"#, keys, regex::escape(v)),
+        None => format!(r#"(?m)(?:^|[\s{{,\[.])["']?({})["']?\s*[:=\]]"#, keys)
+    };
+    let re = Regex::new(&pattern).ok()?;
+    let found = re.captures_iter(text).filter(|c| {
+        //值之后只能是行尾或分隔符，避免只匹配到值的前缀
+        let rest = text[c.get(0).unwrap().end()..].split('\n').next().unwrap_or("").trim_start();
+        value.is_none() || rest.is_empty() || rest.starts_with([',', '}', ']', '#'])
+    }).nth(nth).map(|c| offset_to_location(text, c.get(1).unwrap().start()));
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offset_to_line_and_column() {
+        assert_eq!(offset_to_location("ab\ncd", 0), (1, 1));
+        assert_eq!(offset_to_location("ab\ncd", 4), (2, 2));
+        assert_eq!(offset_to_location("中文\nx", 7), (2, 1));
+        assert_eq!(offset_to_location("ab", 100), (1, 3));
+    }
+
+    #[test]
+    fn locate_json() {
+        let text = "{\n  \"tags\": [\n    {\"name\": \"a\"},\n    {\"name\": \"ab\", \"children\": [{\"name\": \"a\"}]}\n  ]\n}";
+        assert_eq!(locate_key(text, &["tags"], Option::None, 0), Option::Some((2, 4)));
+        assert_eq!(locate_key(text, &["name"], Option::Some("a"), 0), Option::Some((3, 7)));
+        assert_eq!(locate_key(text, &["name"], Option::Some("a"), 1), Option::Some((4, 35)));
+        assert_eq!(locate_key(text, &["name"], Option::Some("ab"), 0), Option::Some((4, 7)));
+        assert_eq!(locate_key(text, &["name"], Option::Some("b"), 0), Option::None);
+        assert_eq!(locate_key(text, &["name"], Option::Some("a"), 2), Option::None);
+    }
+
+    #[test]
+    fn locate_yaml() {
+        let text = "tags:\n  - name: a\n    children:\n      - name: 'a'\n  - name: a b # comment\nsetting:\n  findSimilar:\n    autoFindSimilar: true\n";
+        assert_eq!(locate_key(text, &["name"], Option::Some("a"), 0), Option::Some((2, 5)));
+        assert_eq!(locate_key(text, &["name"], Option::Some("a"), 1), Option::Some((4, 9)));
+        assert_eq!(locate_key(text, &["name"], Option::Some("a b"), 0), Option::Some((5, 5)));
+        assert_eq!(locate_key(text, &["find_similar", "findSimilar"], Option::None, 0), Option::Some((7, 3)));
+    }
+
+    #[test]
+    fn locate_toml() {
+        let text = "[[tags]]\nname = \"a\"\n\n[setting.meta]\nauthorColors = { x = \"red\", y = 'blue' }\n\n[setting.find_similar]\nx = 1\n";
+        assert_eq!(locate_key(text, &["name"], Option::Some("a"), 0), Option::Some((2, 1)));
+        assert_eq!(locate_key(text, &["tags"], Option::None, 0), Option::Some((1, 3)));
+        assert_eq!(locate_key(text, &["meta"], Option::None, 0), Option::Some((4, 10)));
+        assert_eq!(locate_key(text, &["find_similar", "findSimilar"], Option::None, 0), Option::Some((7, 10)));
+        assert_eq!(locate_key(text, &["y"], Option::Some("blue"), 0), Option::Some((5, 29)));
+        assert_eq!(locate_key(text, &["x"], Option::Some("red"), 0), Option::Some((5, 18)));
+    }
+}
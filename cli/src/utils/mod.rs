@@ -1 +1,4 @@
-pub mod error;
\ No newline at end of file
+pub mod error;
+pub mod deserialize;
+pub mod diff;
+pub mod schema;pub mod location;
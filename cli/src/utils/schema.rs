@@ -0,0 +1,232 @@
+use std::{cell::Cell, fmt};
+use schemars::schema::{Schema, SchemaObject};
+use serde::de::{self, DeserializeOwned, DeserializeSeed, Deserializer, IntoDeserializer, MapAccess, SeqAccess, Visitor};
+use serde_json::json;
+
+
+/// 把T的字段别名加入schema中。别名与原字段使用相同的schema；必填字段改为原名与别名任选其一。
+/// 别名是通过serde反序列化的过程探测出来的，因此总是与serde的定义保持一致。
+pub fn add_aliases<T: DeserializeOwned>(schema: &mut SchemaObject) {
+    let aliases = field_aliases::<T>();
+    let mut required_groups: Vec<Vec<String>> = Vec::new();
+    if let Some(ref mut object) = schema.object {
+        for (alias, field) in aliases.iter() {
+            if let Some(s) = object.properties.get(field).cloned() {
+                object.properties.insert(alias.clone(), s);
+            }
+            if object.required.contains(field) {
+                match required_groups.iter_mut().find(|g| g[0] == *field) {
+                    Some(g) => g.push(alias.clone()),
+                    None => required_groups.push(vec![field.clone(), alias.clone()])
+                }
+            }
+        }
+        for group in required_groups.iter() {
+            object.required.remove(&group[0]);
+        }
+    }
+    for group in required_groups {
+        let any_of: Schema = serde_json::from_value(json!({ "anyOf": group.iter().map(|n| json!({ "required": [n] })).collect::<Vec<_>>() })).unwrap();
+        schema.subschemas().all_of.get_or_insert_with(Vec::new).push(any_of);
+    }
+}
+
+/// 探测T的所有字段别名，返回(别名, 字段名)。
+fn field_aliases<T: DeserializeOwned>() -> Vec<(String, String)> {
+    let fields: Cell<&'static [&'static str]> = Cell::new(&[]);
+    let _ = T::deserialize(FieldsProbe { fields: &fields });
+    //同一个key出现两次时，serde会以字段的正式名称报告重复字段
+    fields.get().iter().filter_map(|name| {
+        let e = T::deserialize(DuplicateProbe { key: name }).err()?;
+        let field = e.0.strip_prefix("duplicate field `")?.strip_suffix('`')?;
+        if field != *name { Option::Some((name.to_string(), field.to_string())) }else{ Option::None }
+    }).collect()
+}
+
+#[derive(Debug)]
+struct ProbeError(String);
+
+impl fmt::Display for ProbeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ProbeError {}
+
+impl de::Error for ProbeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        ProbeError(msg.to_string())
+    }
+}
+
+/// 取得结构体接受的所有字段名，包括别名。
+struct FieldsProbe<'a> {
+    fields: &'a Cell<&'static [&'static str]>
+}
+
+impl <'de, 'a> Deserializer<'de> for FieldsProbe<'a> {
+    type Error = ProbeError;
+    fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, ProbeError> {
+        Result::Err(de::Error::custom("not a struct"))
+    }
+    fn deserialize_struct<V: Visitor<'de>>(self, _: &'static str, fields: &'static [&'static str], _: V) -> Result<V::Value, ProbeError> {
+        self.fields.set(fields);
+        Result::Err(de::Error::custom("probed"))
+    }
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+/// 提供一个key出现两次的map。
+struct DuplicateProbe {
+    key: &'static str
+}
+
+impl <'de> Deserializer<'de> for DuplicateProbe {
+    type Error = ProbeError;
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        visitor.visit_map(DuplicateKeyMap { key: self.key, count: 0 })
+    }
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct DuplicateKeyMap {
+    key: &'static str,
+    count: u8
+}
+
+impl <'de> MapAccess<'de> for DuplicateKeyMap {
+    type Error = ProbeError;
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, ProbeError> {
+        if self.count >= 2 { return Result::Ok(Option::None) }
+        self.count += 1;
+        seed.deserialize(self.key.into_deserializer()).map(Option::Some)
+    }
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, ProbeError> {
+        seed.deserialize(EmptyValue)
+    }
+}
+
+macro_rules! empty_number {
+    ($($($method:ident),* => $visit:ident($value:expr));*) => {
+        $($(fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+            visitor.$visit($value)
+        })*)*
+    };
+}
+
+/// 对任何类型都给出一个最简单的值，使字段值的解析总能成功。
+struct EmptyValue;
+
+impl <'de> Deserializer<'de> for EmptyValue {
+    type Error = ProbeError;
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        visitor.visit_str("")
+    }
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        visitor.visit_none()
+    }
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        visitor.visit_bool(false)
+    }
+    empty_number! {
+        deserialize_i8, deserialize_i16, deserialize_i32, deserialize_i64, deserialize_i128 => visit_i64(0);
+        deserialize_u8, deserialize_u16, deserialize_u32, deserialize_u64, deserialize_u128 => visit_u64(0);
+        deserialize_f32, deserialize_f64 => visit_f64(0.0)
+    }
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        visitor.visit_unit()
+    }
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        visitor.visit_seq(EmptyAccess)
+    }
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        visitor.visit_map(EmptyAccess)
+    }
+    fn deserialize_struct<V: Visitor<'de>>(self, _: &'static str, _: &'static [&'static str], visitor: V) -> Result<V::Value, ProbeError> {
+        visitor.visit_map(EmptyAccess)
+    }
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _: &'static str, visitor: V) -> Result<V::Value, ProbeError> {
+        visitor.visit_newtype_struct(self)
+    }
+    serde::forward_to_deserialize_any! {
+        char str string bytes byte_buf unit_struct tuple tuple_struct enum identifier ignored_any
+    }
+}
+
+struct EmptyAccess;
+
+impl <'de> SeqAccess<'de> for EmptyAccess {
+    type Error = ProbeError;
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, _: T) -> Result<Option<T::Value>, ProbeError> {
+        Result::Ok(Option::None)
+    }
+}
+
+impl <'de> MapAccess<'de> for EmptyAccess {
+    type Error = ProbeError;
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, _: K) -> Result<Option<K::Value>, ProbeError> {
+        Result::Ok(Option::None)
+    }
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, _: V) -> Result<V::Value, ProbeError> {
+        Result::Err(de::Error::custom("no value"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use schemars::{schema_for, JsonSchema};
+    use serde::Deserialize;
+    use crate::module::api::bulk::{SourceDataBulkForm, TagBulkForm};
+    use super::*;
+
+    fn schema_with_aliases<T: JsonSchema + DeserializeOwned>() -> serde_json::Value {
+        let mut root = schema_for!(T);
+        add_aliases::<T>(&mut root.schema);
+        serde_json::to_value(&root.schema).unwrap()
+    }
+
+    #[test]
+    fn probe_field_aliases() {
+        #[derive(Deserialize)]
+        #[allow(dead_code)]
+        struct Form {
+            name: String,
+            #[serde(rename = "otherNames", alias = "other_names", alias = "alias")]
+            other_names: Vec<String>,
+            #[serde(rename = "type")]
+            form_type: Option<i32>
+        }
+        let mut aliases = field_aliases::<Form>();
+        aliases.sort();
+        assert_eq!(aliases, vec![("alias".to_string(), "otherNames".to_string()), ("other_names".to_string(), "otherNames".to_string())]);
+    }
+
+    #[test]
+    fn optional_aliases_added() {
+        let schema = schema_with_aliases::<TagBulkForm>();
+        let properties = schema["properties"].as_object().unwrap();
+        assert!(properties.contains_key("otherNames"));
+        assert_eq!(properties["other_names"], properties["otherNames"]);
+        assert!(properties.contains_key("mapping"));
+        assert_eq!(schema["required"], json!(["name"]));
+        assert!(schema.get("allOf").is_none());
+    }
+
+    #[test]
+    fn required_aliases_any_of() {
+        let schema = schema_with_aliases::<SourceDataBulkForm>();
+        let required = schema["required"].as_array().cloned().unwrap_or_default();
+        assert!(!required.contains(&json!("sourceSite")) && !required.contains(&json!("sourceId")));
+        assert!(schema["allOf"].as_array().unwrap().contains(&json!({ "anyOf": [{ "required": ["sourceSite"] }, { "required": ["site"] }, { "required": ["source_site"] }] })));
+        assert!(schema["allOf"].as_array().unwrap().contains(&json!({ "anyOf": [{ "required": ["sourceId"] }, { "required": ["id"] }, { "required": ["source_id"] }] })));
+    }
+}